@@ -0,0 +1,191 @@
+use bitboard::{self, Bitboard};
+use board::Board;
+use color::Color;
+use moves::Move;
+use piece::Type;
+use square::Square;
+
+/// Queries about which pieces attack which squares, answered from the
+/// bitboards without making any moves.
+impl Board {
+    /// the pieces of the given color attacking the square
+    pub fn attackers_to(&self, square: Square, color: Color) -> Bitboard {
+        self.attackers_to_occupied(square, color, self.occupancy())
+    }
+
+    /// the pieces of the given color attacking the square, with sliders
+    /// blocked by the given occupancy rather than the board's
+    pub fn attackers_to_occupied(&self, square: Square, color: Color, occupancy: Bitboard) -> Bitboard {
+        let queens = self.pieces_of(color, Type::Queen);
+        // a piece on the square would attack the pieces that attack it
+        bitboard::pawn_attacks(color.opposite(), square) & self.pieces_of(color, Type::Pawn) |
+            bitboard::knight_attacks(square) & self.pieces_of(color, Type::Knight) |
+            bitboard::king_attacks(square) & self.pieces_of(color, Type::King) |
+            bitboard::bishop_attacks(square, occupancy) & (self.pieces_of(color, Type::Bishop) | queens) |
+            bitboard::rook_attacks(square, occupancy) & (self.pieces_of(color, Type::Rook) | queens)
+    }
+
+    /// returns true if any piece of the given color attacks the square
+    pub fn is_attacked(&self, square: Square, by: Color) -> bool {
+        self.attackers_to(square, by) != bitboard::EMPTY
+    }
+
+    /// the enemy pieces giving check to the side to move
+    pub fn checkers(&self) -> Bitboard {
+        match self.king_square(self.active_color) {
+            Some(king) => self.attackers_to(king, self.active_color.opposite()),
+            None => bitboard::EMPTY
+        }
+    }
+
+    /// the pieces of the given color that are the only thing between their
+    /// king and an enemy slider
+    pub fn pinned(&self, color: Color) -> Bitboard {
+        let king = match self.king_square(color) {
+            Some(king) => king,
+            None => return bitboard::EMPTY
+        };
+        let enemy = color.opposite();
+        let queens = self.pieces_of(enemy, Type::Queen);
+        // the sliders that would attack the king if nothing were in the way
+        let snipers = bitboard::rook_attacks(king, bitboard::EMPTY) & (self.pieces_of(enemy, Type::Rook) | queens) |
+            bitboard::bishop_attacks(king, bitboard::EMPTY) & (self.pieces_of(enemy, Type::Bishop) | queens);
+        let occupancy = self.occupancy();
+        let mut pinned = bitboard::EMPTY;
+        for sniper in bitboard::squares(snipers) {
+            let blockers = bitboard::between(king, sniper) & occupancy;
+            if blockers.count_ones() == 1 {
+                pinned |= blockers & self.occupied[color as usize];
+            }
+        }
+        pinned
+    }
+
+    /// the en passant square if the side to move can legally capture on it.
+    /// Only then does it count as part of the position, for hashing and so
+    /// for repetitions.
+    pub fn en_passant_target(&self) -> Option<Square> {
+        let square = self.en_passant_square?;
+        let us = self.active_color;
+        // the pawn that moved two squares is just past the square it skipped
+        let pushed = if us == Color::White { square.rank() - 1 } else { square.rank() + 1 };
+        let pushed = bitboard::from_square(Square::new(square.file(), pushed));
+        let capturers = bitboard::pawn_attacks(us.opposite(), square) & self.pieces_of(us, Type::Pawn);
+        let legal = bitboard::squares(capturers).any(|from| match self.king_square(us) {
+            Some(king) => {
+                // both pawns leave the rank, which can uncover a slider
+                let occupancy = (self.occupancy() ^ bitboard::from_square(from) ^ pushed) |
+                    bitboard::from_square(square);
+                self.attackers_to_occupied(king, us.opposite(), occupancy) & !pushed == bitboard::EMPTY
+            },
+            None => true
+        });
+        if legal { Some(square) } else { None }
+    }
+
+    /// returns true if the move, which must be legal, would check the
+    /// enemy king, either with the piece moved or by uncovering a slider
+    pub fn gives_check(&self, m: Move) -> bool {
+        let color = self.active_color;
+        let king = match self.king_square(color.opposite()) {
+            Some(king) => king,
+            None => return false
+        };
+        let piece_type = match self.piece_at(m.from) {
+            Some(piece) => m.promotion.unwrap_or(piece.piece_type),
+            None => return false
+        };
+        let mut occupancy = self.occupancy() & !bitboard::from_square(m.from) | bitboard::from_square(m.to);
+        // the pieces that moved, which can't give a discovered check
+        let mut moved = bitboard::from_square(m.from);
+        if m.is_en_passant() {
+            occupancy &= !bitboard::from_square(Square::new(m.to.file(), m.from.rank()));
+        }
+        if m.is_castle() {
+            // the king "captures" its rook, and both then move
+            let (king_to, rook_to) = m.castling_squares();
+            occupancy = occupancy & !bitboard::from_square(m.to) | bitboard::from_square(king_to) |
+                bitboard::from_square(rook_to);
+            moved |= bitboard::from_square(m.to);
+            if bitboard::contains(bitboard::rook_attacks(rook_to, occupancy), king) {
+                return true
+            }
+        }
+
+        let direct = match piece_type {
+            Type::Pawn => bitboard::pawn_attacks(color, m.to),
+            Type::Knight => bitboard::knight_attacks(m.to),
+            Type::Bishop => bitboard::bishop_attacks(m.to, occupancy),
+            Type::Rook => bitboard::rook_attacks(m.to, occupancy),
+            Type::Queen => bitboard::queen_attacks(m.to, occupancy),
+            Type::King => bitboard::EMPTY
+        };
+        if bitboard::contains(direct, king) {
+            return true
+        }
+        let queens = self.pieces_of(color, Type::Queen);
+        let discovered = bitboard::bishop_attacks(king, occupancy) & (self.pieces_of(color, Type::Bishop) | queens) |
+            bitboard::rook_attacks(king, occupancy) & (self.pieces_of(color, Type::Rook) | queens);
+        discovered & !moved != bitboard::EMPTY
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bitboard;
+    use board::Board;
+    use color::Color;
+    use logic;
+    use square::Square;
+
+    fn square(notation: &str) -> Square {
+        notation.parse().unwrap()
+    }
+
+    #[test]
+    fn test_attackers_to() {
+        let board = Board::from_fen("4k3/8/8/3p4/8/1BN5/8/3RK3 w - - 0 1").unwrap();
+        // the bishop, knight and rook all bear on d5 but the pawn doesn't
+        let attackers = board.attackers_to(square("d5"), Color::White);
+        assert_eq!(attackers, bitboard::from_square(square("b3")) | bitboard::from_square(square("c3")) |
+                   bitboard::from_square(square("d1")));
+        assert!(board.is_attacked(square("c4"), Color::Black));
+        assert!(!board.is_attacked(square("d4"), Color::Black));
+    }
+
+    #[test]
+    fn test_checkers_and_pinned() {
+        let board = Board::from_fen("4k3/8/8/b7/8/8/3P4/r3K3 w - - 0 1").unwrap();
+        assert_eq!(board.checkers(), bitboard::from_square(square("a1")));
+        assert_eq!(board.pinned(Color::White), bitboard::from_square(square("d2")));
+        assert_eq!(board.pinned(Color::Black), bitboard::EMPTY);
+    }
+
+    #[test]
+    fn test_gives_check() {
+        let check = |fen: &str, m: &str| {
+            let board = Board::from_fen(fen).unwrap();
+            board.gives_check(logic::parse_move(&board, m).unwrap())
+        };
+        // direct, discovered and quiet
+        assert!(check("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1a8"));
+        assert!(check("4k3/8/8/8/4N3/8/8/4RK2 w - - 0 1", "e4c5"));
+        assert!(!check("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1a7"));
+        // a promotion, a castle and an en passant that uncovers the bishop
+        assert!(check("3k4/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"));
+        assert!(!check("3k4/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8n"));
+        assert!(check("5k2/8/8/8/8/8/8/4K2R w K - 0 1", "e1g1"));
+        assert!(check("7k/8/8/3Pp3/8/8/8/B3K3 w - e6 0 1", "d5e6"));
+    }
+
+    #[test]
+    fn test_en_passant_target() {
+        let target = |fen: &str| Board::from_fen(fen).unwrap().en_passant_target();
+        assert_eq!(target("7k/8/8/3Pp3/8/8/8/B3K3 w - e6 0 1"), Some(square("e6")));
+        assert_eq!(target("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1"), Some(square("e3")));
+        // no pawn beside the one that moved
+        assert_eq!(target("4k3/8/8/4p3/8/8/8/4K3 w - e6 0 1"), None);
+        // taking would leave the king open to the rook along the rank
+        assert_eq!(target("7k/8/8/K2Pp2r/8/8/8/8 w - e6 0 1"), None);
+    }
+}
@@ -0,0 +1,165 @@
+use color::Color;
+use magic;
+use square::Square;
+
+/// A set of squares, one bit per square.
+/// Bit 0 is a1, bit 7 is h1 and bit 63 is h8.
+pub type Bitboard = u64;
+
+pub const EMPTY: Bitboard = 0;
+
+/// the squares the same color as a1
+pub const DARK_SQUARES: Bitboard = 0xaa55_aa55_aa55_aa55;
+
+/// returns the bitboard with only the given square set
+pub fn from_square(square: Square) -> Bitboard {
+    1u64 << square.index()
+}
+
+/// returns true if the given square is set in the bitboard
+pub fn contains(bitboard: Bitboard, square: Square) -> bool {
+    bitboard & from_square(square) != 0
+}
+
+/// iterates over every set square of a bitboard, from a1 to h8
+pub struct Squares {
+    remaining: Bitboard
+}
+
+impl Iterator for Squares {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Square> {
+        if self.remaining == 0 {
+            return None
+        }
+        let index = self.remaining.trailing_zeros() as u8;
+        // clear the lowest set bit
+        self.remaining &= self.remaining - 1;
+        Some(Square::from_index(index))
+    }
+}
+
+pub fn squares(bitboard: Bitboard) -> Squares {
+    Squares { remaining: bitboard }
+}
+
+/// the bitboard of every square reachable by one of the given (file, rank) steps
+const fn step_attacks(index: usize, steps: &[(i8, i8)]) -> Bitboard {
+    let file = (index % 8) as i8;
+    let rank = (index / 8) as i8;
+    let mut attacks = EMPTY;
+    let mut i = 0;
+    while i < steps.len() {
+        let new_file = file + steps[i].0;
+        let new_rank = rank + steps[i].1;
+        if new_file >= 0 && new_file < 8 && new_rank >= 0 && new_rank < 8 {
+            attacks |= 1 << (new_rank * 8 + new_file);
+        }
+        i += 1;
+    }
+    attacks
+}
+
+const fn step_table(steps: &[(i8, i8)]) -> [Bitboard; 64] {
+    let mut table = [EMPTY; 64];
+    let mut i = 0;
+    while i < 64 {
+        table[i] = step_attacks(i, steps);
+        i += 1;
+    }
+    table
+}
+
+const KNIGHT_STEPS: [(i8, i8); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+const KING_STEPS: [(i8, i8); 8] = [(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)];
+
+static KNIGHT_ATTACKS: [Bitboard; 64] = step_table(&KNIGHT_STEPS);
+static KING_ATTACKS: [Bitboard; 64] = step_table(&KING_STEPS);
+/// indexed by `Color as usize`, white pawns capture towards the 8th rank
+static PAWN_ATTACKS: [[Bitboard; 64]; 2] = [step_table(&[(-1, 1), (1, 1)]), step_table(&[(-1, -1), (1, -1)])];
+
+pub fn knight_attacks(square: Square) -> Bitboard {
+    KNIGHT_ATTACKS[square.index()]
+}
+
+pub fn king_attacks(square: Square) -> Bitboard {
+    KING_ATTACKS[square.index()]
+}
+
+/// the squares a pawn of the given color on the given square attacks
+pub fn pawn_attacks(color: Color, square: Square) -> Bitboard {
+    PAWN_ATTACKS[color as usize][square.index()]
+}
+
+/// the squares a bishop attacks, stopping at the first piece in each direction
+pub fn bishop_attacks(square: Square, occupancy: Bitboard) -> Bitboard {
+    magic::bishop_attacks(square, occupancy)
+}
+
+/// the squares a rook attacks, stopping at the first piece in each direction
+pub fn rook_attacks(square: Square, occupancy: Bitboard) -> Bitboard {
+    magic::rook_attacks(square, occupancy)
+}
+
+pub fn queen_attacks(square: Square, occupancy: Bitboard) -> Bitboard {
+    bishop_attacks(square, occupancy) | rook_attacks(square, occupancy)
+}
+
+/// `BETWEEN[a][b]` is the squares strictly between a and b if they share a
+/// rank, file or diagonal, and empty otherwise
+static BETWEEN: [[Bitboard; 64]; 64] = line_tables().0;
+/// `LINE[a][b]` is the whole rank, file or diagonal through a and b, and
+/// empty if they don't share one
+static LINE: [[Bitboard; 64]; 64] = line_tables().1;
+
+const fn line_tables() -> ([[Bitboard; 64]; 64], [[Bitboard; 64]; 64]) {
+    let mut between = [[EMPTY; 64]; 64];
+    let mut line = [[EMPTY; 64]; 64];
+    let mut a = 0;
+    while a < 64 {
+        let mut d = 0;
+        while d < 8 {
+            let (file_step, rank_step) = KING_STEPS[d];
+            // the full line is this ray, the opposite ray and the square itself
+            let full = ray(a, file_step, rank_step) | ray(a, -file_step, -rank_step) | 1 << a;
+            let mut file = (a % 8) as i8 + file_step;
+            let mut rank = (a / 8) as i8 + rank_step;
+            let mut passed = EMPTY;
+            while file >= 0 && file < 8 && rank >= 0 && rank < 8 {
+                let b = (rank * 8 + file) as usize;
+                between[a][b] = passed;
+                line[a][b] = full;
+                passed |= 1 << b;
+                file += file_step;
+                rank += rank_step;
+            }
+            d += 1;
+        }
+        a += 1;
+    }
+    (between, line)
+}
+
+/// every square from the given square to the edge in one direction
+const fn ray(index: usize, file_step: i8, rank_step: i8) -> Bitboard {
+    let mut attacks = EMPTY;
+    let mut file = (index % 8) as i8 + file_step;
+    let mut rank = (index / 8) as i8 + rank_step;
+    while file >= 0 && file < 8 && rank >= 0 && rank < 8 {
+        attacks |= 1 << (rank * 8 + file);
+        file += file_step;
+        rank += rank_step;
+    }
+    attacks
+}
+
+/// the squares strictly between the two, if they are on a common line
+pub fn between(a: Square, b: Square) -> Bitboard {
+    BETWEEN[a.index()][b.index()]
+}
+
+/// the rank, file or diagonal running through both squares, if any
+pub fn line(a: Square, b: Square) -> Bitboard {
+    LINE[a.index()][b.index()]
+}
@@ -1,59 +1,50 @@
+use bitboard::{self, Bitboard};
 use piece::{Piece, Type};
 use color::Color;
+use moves::Move;
 use std::char;
+use std::error::Error;
+use std::fmt;
+use square::Square;
+use zobrist;
 
-/// 0 <= file, rank <= 7
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-pub struct Location {
-    pub file: u8,
-    pub rank: u8
-}
+/// the FEN of the standard starting position
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
-impl Location {
-    pub fn to_notation(&self) -> String {
-        let mut output = "".to_owned();
-        match self.file {
-            0 => output.push('a'),
-            1 => output.push('b'),
-            2 => output.push('c'),
-            3 => output.push('d'),
-            4 => output.push('e'),
-            5 => output.push('f'),
-            6 => output.push('g'),
-            7 => output.push('h'),
-            _ => ()
-        }
-        output.push(char::from_digit((self.rank + 1) as u32, 10).unwrap());
-        output
+/// the FEN of Chess960 start position `index`, numbered from 0 to 959 in
+/// Scharnagl's scheme so that 518 is the standard start position
+pub fn chess960_fen(index: u16) -> Option<String> {
+    if index >= 960 {
+        return None
     }
-
-    pub fn parse_notation(s: &str) -> Location {
-        let mut output_location = Location { file: 0, rank: 0 };
-        let string = s.to_string();
-        let chars = string.chars();
-        for ch in chars {
-            match ch {
-                'a' => output_location.file = 0,
-                'b' => output_location.file = 1,
-                'c' => output_location.file = 2,
-                'd' => output_location.file = 3,
-                'e' => output_location.file = 4,
-                'f' => output_location.file = 5,
-                'g' => output_location.file = 6,
-                'h' => output_location.file = 7,
-                '1' => output_location.rank = 0,
-                '2' => output_location.rank = 1,
-                '3' => output_location.rank = 2,
-                '4' => output_location.rank = 3,
-                '5' => output_location.rank = 4,
-                '6' => output_location.rank = 5,
-                '7' => output_location.rank = 6,
-                '8' => output_location.rank = 7,
-                _ => panic!("location parse_notation")
-            }
-        }
-        output_location
+    // the ways to place two knights on five squares
+    const KNIGHTS: [(usize, usize); 10] = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3),
+                                           (2, 4), (3, 4)];
+    let mut back_rank = [None; 8];
+    let mut n = index as usize;
+    // one bishop on each color, then the queen, knights and finally the
+    // king between the two rooks on whatever squares are left
+    back_rank[n % 4 * 2 + 1] = Some(Type::Bishop);
+    n /= 4;
+    back_rank[n % 4 * 2] = Some(Type::Bishop);
+    n /= 4;
+    let empty = |back_rank: &[Option<Type>; 8]| {
+        (0..8).filter(|&file| back_rank[file].is_none()).collect::<Vec<_>>()
+    };
+    back_rank[empty(&back_rank)[n % 6]] = Some(Type::Queen);
+    n /= 6;
+    let files = empty(&back_rank);
+    let (first, second) = KNIGHTS[n];
+    back_rank[files[first]] = Some(Type::Knight);
+    back_rank[files[second]] = Some(Type::Knight);
+    for (&file, &piece_type) in empty(&back_rank).iter().zip(&[Type::Rook, Type::King, Type::Rook]) {
+        back_rank[file] = Some(piece_type);
     }
+
+    let rank = |color| back_rank.iter().map(|&piece_type| {
+        Piece { piece_type: piece_type.unwrap(), color }.to_fen_char()
+    }).collect::<String>();
+    Some(format!("{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1", rank(Color::Black), rank(Color::White)))
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -64,29 +55,115 @@ pub struct CastlingAvailability {
     pub black_queenside: bool
 }
 
+impl CastlingAvailability {
+    /// returns true if the color may still castle on the given side
+    pub fn get(self, color: Color, kingside: bool) -> bool {
+        match (color, kingside) {
+            (Color::White, true) => self.white_kingside,
+            (Color::White, false) => self.white_queenside,
+            (Color::Black, true) => self.black_kingside,
+            (Color::Black, false) => self.black_queenside
+        }
+    }
+
+    pub fn set(&mut self, color: Color, kingside: bool, value: bool) {
+        match (color, kingside) {
+            (Color::White, true) => self.white_kingside = value,
+            (Color::White, false) => self.white_queenside = value,
+            (Color::Black, true) => self.black_kingside = value,
+            (Color::Black, false) => self.black_queenside = value
+        }
+    }
+
+    /// the castling rights as a 4 bit mask, in KQkq order from the lowest bit
+    pub fn bits(self) -> usize {
+        self.white_kingside as usize | (self.white_queenside as usize) << 1 |
+            (self.black_kingside as usize) << 2 | (self.black_queenside as usize) << 3
+    }
+}
+
+/// the reasons a FEN string can fail to parse
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FenError {
+    /// the FEN had this many fields instead of 4 or 6
+    FieldCount(usize),
+    /// the piece placement had this many ranks instead of 8
+    RankCount(usize),
+    /// the given rank (1 to 8) did not describe exactly 8 squares
+    RankLength(u8),
+    /// the piece placement contained a character that is not a piece
+    UnknownPiece(char),
+    /// the active color was neither 'w' nor 'b'
+    SideToMove(String),
+    /// the castling field was not '-' or a combination of 'KQkq' and rook
+    /// files, as in X-FEN and Shredder-FEN
+    Castling(String),
+    /// the en passant field was not '-' or a square on the 3rd or 6th rank
+    EnPassant(String),
+    /// the halfmove clock was not a number
+    HalfmoveClock(String),
+    /// the fullmove number was not a number
+    FullmoveNumber(String)
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FenError::FieldCount(n) => write!(f, "expected 4 or 6 fields, found {}", n),
+            FenError::RankCount(n) => write!(f, "expected 8 ranks, found {}", n),
+            FenError::RankLength(rank) => write!(f, "rank {} does not have 8 squares", rank),
+            FenError::UnknownPiece(ch) => write!(f, "unknown piece '{}'", ch),
+            FenError::SideToMove(ref s) => write!(f, "invalid side to move '{}'", s),
+            FenError::Castling(ref s) => write!(f, "invalid castling availability '{}'", s),
+            FenError::EnPassant(ref s) => write!(f, "invalid en passant square '{}'", s),
+            FenError::HalfmoveClock(ref s) => write!(f, "invalid halfmove clock '{}'", s),
+            FenError::FullmoveNumber(ref s) => write!(f, "invalid fullmove number '{}'", s),
+        }
+    }
+}
+
+impl Error for FenError {}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct Board {
-    pub board: [[Option<Piece>; 8]; 8],
+    /// one bitboard per piece, indexed by `Color as usize` and then `Type as usize`
+    pub pieces: [[Bitboard; 6]; 2],
+    /// every square occupied by a piece of the given color
+    pub occupied: [Bitboard; 2],
     pub active_color: Color,
     pub castling_availability: CastlingAvailability,
-    pub en_passant_square: Option<Location>,
-    pub halfmove_clock: u8,
-    pub fullmove_number: u8
+    /// the files the castling rooks started on, indexed by `Color as usize`
+    /// and then 0 for kingside and 1 for queenside. Always h and a except
+    /// in Chess960.
+    pub castling_rook_files: [[u8; 2]; 2],
+    pub en_passant_square: Option<Square>,
+    pub halfmove_clock: u16,
+    pub fullmove_number: u16,
+    /// zobrist hash of the position, kept up to date as moves are made
+    pub hash: u64
+}
+
+/// everything `make_move` changes that can't be recomputed from the move itself
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Undo {
+    /// the piece that moved, before any promotion
+    pub moved: Option<Piece>,
+    /// the piece that was captured, including a pawn taken en passant
+    pub captured: Option<Piece>,
+    pub castling_availability: CastlingAvailability,
+    pub en_passant_square: Option<Square>,
+    pub halfmove_clock: u16,
+    pub fullmove_number: u16,
+    pub hash: u64
 }
 
 impl Board {
-    pub fn from_fen(fen: &str) -> Board {
+    /// parses a FEN string. The halfmove clock and fullmove number may be
+    /// left out, in which case they default to 0 and 1.
+    pub fn from_fen(fen: &str) -> Result<Board, FenError> {
         let mut output_board = Board {
-            board: [
-                [None; 8],
-                [None; 8],
-                [None; 8],
-                [None; 8],
-                [None; 8],
-                [None; 8],
-                [None; 8],
-                [None; 8],
-            ],
+            pieces: [[bitboard::EMPTY; 6]; 2],
+            occupied: [bitboard::EMPTY; 2],
             active_color: Color::White,
             castling_availability: CastlingAvailability {
                 white_kingside: false,
@@ -94,186 +171,433 @@ impl Board {
                 black_kingside: false,
                 black_queenside: false
             },
+            castling_rook_files: [[7, 0]; 2],
             en_passant_square: None,
             halfmove_clock: 0,
-            fullmove_number: 0
+            fullmove_number: 1,
+            hash: 0
         };
         // piece placement, active color, castling availability,
         // en passant target square, halfmove clock, fullmove number
         let split_fen = fen.split_whitespace().collect::<Vec<_>>();
-        if split_fen.len() != 6 {
-            // TODO: error here
+        if split_fen.len() != 4 && split_fen.len() != 6 {
+            return Err(FenError::FieldCount(split_fen.len()));
         }
         let piece_placement = split_fen[0];
         let active_color = split_fen[1];
         let castling_availability = split_fen[2];
         let en_passant_target_square = split_fen[3];
-        let halfmove_clock = split_fen[4];
-        let fullmove_number = split_fen[5];
 
-        let ranks = piece_placement.split("/").collect::<Vec<_>>();
-        for i in 0..ranks.len() {
-            let rank = ranks[i];
-            Board::parse_rank(&mut output_board, rank, 7 - i);
+        let ranks = piece_placement.split('/').collect::<Vec<_>>();
+        if ranks.len() != 8 {
+            return Err(FenError::RankCount(ranks.len()));
         }
-
-        if active_color == "b" {
-           output_board.active_color = Color::Black;
+        for (i, rank) in ranks.iter().enumerate() {
+            Board::parse_rank(&mut output_board, rank, 7 - i)?;
         }
 
-        let castle = castling_availability.to_string();
-        if castle.contains("K") {
-            output_board.castling_availability.white_kingside = true;
-        }
-        if castle.contains("Q") {
-            output_board.castling_availability.white_queenside = true;
-        }
-        if castle.contains("k") {
-            output_board.castling_availability.black_kingside = true;
-        }
-        if castle.contains("q") {
-            output_board.castling_availability.black_queenside = true;
+        output_board.active_color = match active_color {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err(FenError::SideToMove(active_color.to_owned()))
+        };
+
+        if castling_availability != "-" {
+            let error = || FenError::Castling(castling_availability.to_owned());
+            for ch in castling_availability.chars() {
+                let color = if ch.is_ascii_uppercase() { Color::White } else { Color::Black };
+                let king_file = output_board.home_king(color).map_or(4, |king| king.file());
+                // KQkq mean the outermost rook on that side, while a file
+                // names the rook, which Chess960 needs when there are two
+                let (kingside, file) = match ch.to_ascii_lowercase() {
+                    'k' => (true, output_board.outer_rook_file(color, true).unwrap_or(7)),
+                    'q' => (false, output_board.outer_rook_file(color, false).unwrap_or(0)),
+                    file @ 'a'..='h' if file as u8 - b'a' != king_file => {
+                        (file as u8 - b'a' > king_file, file as u8 - b'a')
+                    },
+                    _ => return Err(error())
+                };
+                // each right may only be listed once
+                if output_board.castling_availability.get(color, kingside) {
+                    return Err(error());
+                }
+                output_board.castling_availability.set(color, kingside, true);
+                output_board.castling_rook_files[color as usize][!kingside as usize] = file;
+            }
         }
 
         if en_passant_target_square != "-" {
-            output_board.en_passant_square = Some
-                (Location::parse_notation(en_passant_target_square));
+            match en_passant_target_square.parse::<Square>() {
+                Ok(square) if square.rank() == 2 || square.rank() == 5 => {
+                    output_board.en_passant_square = Some(square)
+                },
+                _ => return Err(FenError::EnPassant(en_passant_target_square.to_owned()))
+            }
         }
 
-        let halfmove_clock_string = halfmove_clock.to_string();
-        let halfmove = halfmove_clock_string.parse::<u8>().unwrap();
-        output_board.halfmove_clock = halfmove;
-
-        let fullmove_number_string = fullmove_number.to_string();
-        let fullmove = fullmove_number_string.parse::<u8>().unwrap();
-        output_board.fullmove_number = fullmove;
+        if split_fen.len() == 6 {
+            let halfmove_clock = split_fen[4];
+            let fullmove_number = split_fen[5];
+            output_board.halfmove_clock = halfmove_clock.parse::<u16>()
+                .map_err(|_| FenError::HalfmoveClock(halfmove_clock.to_owned()))?;
+            output_board.fullmove_number = fullmove_number.parse::<u16>()
+                .map_err(|_| FenError::FullmoveNumber(fullmove_number.to_owned()))?;
+        }
 
-        output_board
+        output_board.hash = zobrist::hash(&output_board);
+        Ok(output_board)
     }
 
-    fn parse_rank(output_board: &mut Board, rank_str: &str, rank: usize) {
-        let rank_string = String::from(rank_str);
-        let chars = rank_string.chars();
-        let mut new_rank: [Option<Piece>; 8] = [None; 8];
-        let mut index = 0;
-        for ch in chars {
-            match ch {
+    fn parse_rank(output_board: &mut Board, rank_str: &str, rank: usize) -> Result<(), FenError> {
+        let bad_length = FenError::RankLength(rank as u8 + 1);
+        let mut file = 0;
+        for ch in rank_str.chars() {
+            if file >= 8 {
+                return Err(bad_length);
+            }
+            let piece = match ch {
                 // convert ascii into number
-                '1'...'8' => index += ch as usize - 49,
+                '1'..='8' => {
+                    file += ch as u8 - b'0';
+                    continue;
+                },
 
-                'r' => new_rank[index] = Some(Piece {color: Color::Black, piece_type: Type::Rook}),
-                'R' => new_rank[index] = Some(Piece {color: Color::White, piece_type: Type::Rook}),
+                'r' => Piece {color: Color::Black, piece_type: Type::Rook},
+                'R' => Piece {color: Color::White, piece_type: Type::Rook},
 
-                'n' => new_rank[index] = Some(Piece {color: Color::Black, piece_type: Type::Knight}),
-                'N' => new_rank[index] = Some(Piece {color: Color::White, piece_type: Type::Knight}),
+                'n' => Piece {color: Color::Black, piece_type: Type::Knight},
+                'N' => Piece {color: Color::White, piece_type: Type::Knight},
 
-                'b' => new_rank[index] = Some(Piece {color: Color::Black, piece_type: Type::Bishop}),
-                'B' => new_rank[index] = Some(Piece {color: Color::White, piece_type: Type::Bishop}),
+                'b' => Piece {color: Color::Black, piece_type: Type::Bishop},
+                'B' => Piece {color: Color::White, piece_type: Type::Bishop},
 
-                'q' => new_rank[index] = Some(Piece {color: Color::Black, piece_type: Type::Queen}),
-                'Q' => new_rank[index] = Some(Piece {color: Color::White, piece_type: Type::Queen}),
+                'q' => Piece {color: Color::Black, piece_type: Type::Queen},
+                'Q' => Piece {color: Color::White, piece_type: Type::Queen},
 
-                'k' => new_rank[index] = Some(Piece {color: Color::Black, piece_type: Type::King}),
-                'K' => new_rank[index] = Some(Piece {color: Color::White, piece_type: Type::King}),
+                'k' => Piece {color: Color::Black, piece_type: Type::King},
+                'K' => Piece {color: Color::White, piece_type: Type::King},
 
-                'p' => new_rank[index] = Some(Piece {color: Color::Black, piece_type: Type::Pawn}),
-                'P' => new_rank[index] = Some(Piece {color: Color::White, piece_type: Type::Pawn}),
-                _ => panic!("parse_rank")
+                'p' => Piece {color: Color::Black, piece_type: Type::Pawn},
+                'P' => Piece {color: Color::White, piece_type: Type::Pawn},
+                _ => return Err(FenError::UnknownPiece(ch))
             };
-            index += 1;
+            output_board.set_piece(Square::new(file, rank as u8), piece);
+            file += 1;
+        }
+        if file != 8 {
+            return Err(bad_length);
         }
-        output_board.board[rank] = new_rank;
+        Ok(())
     }
 
-    // assumes the move is legal
-    // TODO: update for castling, en passant, etc
-    pub fn after_move(&self, start: Location, end: Location) -> Board {
-        let mut new_board = self.clone();
-        if let Some(p) = new_board.board[start.rank as usize][start.file as usize] {
-            new_board.board[start.rank as usize][start.file as usize] = None;
-            new_board.active_color = 
-                if new_board.active_color == Color::White {Color::Black} else {Color::White};
-            new_board.board[end.rank as usize][end.file as usize] = Some(p);
-            // pawn promotion...auto promotes to queen...need to be flexable
-            // though not a high priority
-            if p.piece_type == Type::Pawn {
-                if end.rank == 7 && p.color == Color::White {
-                    new_board.board[end.rank as usize][end.file as usize] = 
-                        Some(Piece {color: Color::White, piece_type: Type::Queen});
-                } else if end.rank == 0 && p.color == Color::Black {
-                    new_board.board[end.rank as usize][end.file as usize] =
-                        Some(Piece {color: Color::Black, piece_type: Type::Queen});
+    /// serializes the board into all six FEN fields
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.piece_at(Square::new(file, rank)) {
+                    None => empty += 1,
+                    Some(p) => {
+                        if empty > 0 {
+                            fen.push(char::from_digit(empty, 10).unwrap());
+                            empty = 0;
+                        }
+                        fen.push(p.to_fen_char());
+                    }
                 }
             }
-            // castling
-            if p.piece_type == Type::King {
-                // kingside
-                if start.file == 4 && end.file == 6 {
-                    // move rook
-                    let rook = new_board.board[end.rank as usize][7].unwrap();
-                    new_board.board[end.rank as usize][7] = None;
-                    new_board.board[end.rank as usize][5] = Some(rook);
-                } else if start.file == 4 && end.file == 2 {
-                    // queenside
-                    // move rook
-                    let rook = new_board.board[end.rank as usize][0].unwrap();
-                    new_board.board[end.rank as usize][0] = None;
-                    new_board.board[end.rank as usize][3] = Some(rook);
+            if empty > 0 {
+                fen.push(char::from_digit(empty, 10).unwrap());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push_str(match self.active_color {
+            Color::White => " w ",
+            Color::Black => " b "
+        });
+
+        let start = fen.len();
+        for &color in &[Color::White, Color::Black] {
+            for &kingside in &[true, false] {
+                if !self.castling_availability.get(color, kingside) {
+                    continue;
                 }
-                if p.color == Color::White {
-                    new_board.castling_availability.white_kingside = false;
-                    new_board.castling_availability.white_queenside = false;
+                // X-FEN: the usual letter unless an inner rook is the one that castles
+                let file = self.castling_rook_files[color as usize][!kingside as usize];
+                let default = if kingside { 7 } else { 0 };
+                let ch = if file == self.outer_rook_file(color, kingside).unwrap_or(default) {
+                    if kingside { 'K' } else { 'Q' }
                 } else {
-                    new_board.castling_availability.black_kingside = false;
-                    new_board.castling_availability.black_queenside = false;
-                }
+                    (b'A' + file) as char
+                };
+                fen.push(if color == Color::White { ch } else { ch.to_ascii_lowercase() });
             }
-            // update castling availability if rook moved
-            if p.piece_type == Type::Rook {
-                if start.rank == 0 {
-                    if start.file == 7 {
-                        new_board.castling_availability.white_kingside = false;
-                    } else if start.file == 0 {
-                        new_board.castling_availability.white_queenside = false;
-                    }
-                } else if start.rank == 7 {
-                    if start.file == 7 {
-                        new_board.castling_availability.black_kingside = false;
-                    } else if start.file == 0 {
-                        new_board.castling_availability.black_queenside = false;
-                    }
-                }
+        }
+        if fen.len() == start {
+            fen.push('-');
+        }
+
+        match self.en_passant_square {
+            None => fen.push_str(" -"),
+            Some(l) => {
+                fen.push(' ');
+                fen.push_str(&l.to_string());
             }
-            // en passant
-            if let Some(square) = new_board.en_passant_square {
-                if p.piece_type == Type::Pawn && end == square {
-                    // capture the en passant pawn
-                    if p.color == Color::White {
-                        new_board.board[end.rank as usize - 1][end.file as usize] = None;
-                    } else {
-                        new_board.board[end.rank as usize + 1][end.file as usize] = None;
-                    }
-                }
+        }
+
+        fen.push_str(&format!(" {} {}", self.halfmove_clock, self.fullmove_number));
+        fen
+    }
+
+    /// the square the rook that castles on the given side started on
+    pub fn castling_rook(&self, color: Color, kingside: bool) -> Square {
+        let home = if color == Color::White { 0 } else { 7 };
+        Square::new(self.castling_rook_files[color as usize][!kingside as usize], home)
+    }
+
+    /// the king of the given color, if it is on its back rank
+    fn home_king(&self, color: Color) -> Option<Square> {
+        let home = if color == Color::White { 0 } else { 7 };
+        self.king_square(color).filter(|king| king.rank() == home)
+    }
+
+    /// the file of the rook of the given color furthest from its king along
+    /// the back rank on the given side
+    fn outer_rook_file(&self, color: Color, kingside: bool) -> Option<u8> {
+        let king = self.home_king(color)?;
+        let files = bitboard::squares(self.pieces_of(color, Type::Rook))
+            .filter(|rook| rook.rank() == king.rank())
+            .map(|rook| rook.file());
+        if kingside {
+            files.filter(|&file| file > king.file()).max()
+        } else {
+            files.filter(|&file| file < king.file()).min()
+        }
+    }
+
+    /// returns the piece on the given square, if any
+    pub fn piece_at(&self, square: Square) -> Option<Piece> {
+        let mask = bitboard::from_square(square);
+        for &color in &[Color::White, Color::Black] {
+            if self.occupied[color as usize] & mask == 0 {
+                continue;
             }
-            new_board.en_passant_square = None;
-            // update the en_passant_square if needed
-            if p.piece_type == Type::Pawn {
-                if p.color == Color::White {
-                    if end.rank - start.rank == 2 {
-                        new_board.en_passant_square = Some(Location {rank: 2, file: end.file});
-                    }
-                } else if start.rank - end.rank == 2 {
-                    new_board.en_passant_square = Some(Location {rank: 5, file: end.file});
+            for &piece_type in &Type::ALL {
+                if self.pieces[color as usize][piece_type as usize] & mask != 0 {
+                    return Some(Piece { piece_type, color });
                 }
             }
         }
+        None
+    }
+
+    pub fn is_empty(&self, square: Square) -> bool {
+        self.occupancy() & bitboard::from_square(square) == 0
+    }
+
+    /// every occupied square, regardless of color
+    pub fn occupancy(&self) -> Bitboard {
+        self.occupied[Color::White as usize] | self.occupied[Color::Black as usize]
+    }
+
+    /// every square holding the given piece type of the given color
+    pub fn pieces_of(&self, color: Color, piece_type: Type) -> Bitboard {
+        self.pieces[color as usize][piece_type as usize]
+    }
+
+    /// how many pieces of the given type and color are on the board
+    pub fn count(&self, color: Color, piece_type: Type) -> u32 {
+        self.pieces_of(color, piece_type).count_ones()
+    }
+
+    /// the square of the king of the given color, if it has one
+    pub fn king_square(&self, color: Color) -> Option<Square> {
+        bitboard::squares(self.pieces_of(color, Type::King)).next()
+    }
+
+    /// every piece on the board with its square, white's first and then
+    /// grouped by type. The bitboards are the piece lists, so this is
+    /// always in step with the position.
+    pub fn pieces(&self) -> impl Iterator<Item = (Square, Piece)> {
+        let pieces = self.pieces;
+        [Color::White, Color::Black].iter().flat_map(move |&color| {
+            Type::ALL.iter().flat_map(move |&piece_type| {
+                bitboard::squares(pieces[color as usize][piece_type as usize])
+                    .map(move |square| (square, Piece { piece_type, color }))
+            })
+        })
+    }
+
+    /// places a piece on the given square, replacing whatever was there
+    pub fn set_piece(&mut self, square: Square, piece: Piece) {
+        self.remove_piece(square);
+        let mask = bitboard::from_square(square);
+        self.pieces[piece.color as usize][piece.piece_type as usize] |= mask;
+        self.occupied[piece.color as usize] |= mask;
+        self.hash ^= zobrist::KEYS.piece(piece, square);
+    }
+
+    /// clears the given square and returns the piece that was on it
+    pub fn remove_piece(&mut self, square: Square) -> Option<Piece> {
+        let piece = self.piece_at(square);
+        if let Some(p) = piece {
+            let mask = !bitboard::from_square(square);
+            self.pieces[p.color as usize][p.piece_type as usize] &= mask;
+            self.occupied[p.color as usize] &= mask;
+            self.hash ^= zobrist::KEYS.piece(p, square);
+        }
+        piece
+    }
+
+    /// the same position with the colors swapped and the board flipped top
+    /// to bottom, so that it should evaluate exactly the same for the side
+    /// to move
+    pub fn mirror(&self) -> Board {
+        let flip = |square: Square| Square::new(square.file(), 7 - square.rank());
+        let mut output = *self;
+        output.pieces = [[bitboard::EMPTY; 6]; 2];
+        output.occupied = [bitboard::EMPTY; 2];
+        for (square, piece) in self.pieces() {
+            output.set_piece(flip(square), Piece { piece_type: piece.piece_type, color: piece.color.opposite() });
+        }
+        output.active_color = self.active_color.opposite();
+        for &color in &[Color::White, Color::Black] {
+            for &kingside in &[true, false] {
+                let right = self.castling_availability.get(color.opposite(), kingside);
+                output.castling_availability.set(color, kingside, right);
+            }
+        }
+        output.castling_rook_files = [self.castling_rook_files[1], self.castling_rook_files[0]];
+        output.en_passant_square = self.en_passant_square.map(flip);
+        output.hash = zobrist::hash(&output);
+        output
+    }
+
+    // assumes the move is legal
+    pub fn after_move(&self, m: Move) -> Board {
+        let mut new_board = *self;
+        new_board.make_move(m);
         new_board
     }
 
+    /// plays the move on this board and returns what is needed to take it back
+    /// with `unmake_move`. Assumes the move is legal.
+    pub fn make_move(&mut self, m: Move) -> Undo {
+        let (start, end) = (m.from, m.to);
+        let mut undo = Undo {
+            moved: None,
+            captured: None,
+            castling_availability: self.castling_availability,
+            en_passant_square: self.en_passant_square,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            hash: self.hash
+        };
+        let en_passant = self.en_passant_target();
+        let p = match self.remove_piece(start) {
+            Some(p) => p,
+            None => return undo
+        };
+        undo.moved = Some(p);
+        if !m.is_castle() {
+            undo.captured = self.remove_piece(end);
+        }
+        self.hash ^= zobrist::KEYS.castling(self.castling_availability.bits());
+        self.hash ^= zobrist::KEYS.en_passant(en_passant);
+        self.hash ^= zobrist::KEYS.black_to_move();
+
+        self.active_color = self.active_color.opposite();
+        if m.is_castle() {
+            // the king takes its own rook, then both go to their squares
+            let (king_end, rook_end) = m.castling_squares();
+            let rook = self.remove_piece(end).unwrap();
+            self.set_piece(king_end, p);
+            self.set_piece(rook_end, rook);
+        } else {
+            match m.promotion {
+                Some(piece_type) => self.set_piece(end, Piece { piece_type, color: p.color }),
+                None => self.set_piece(end, p)
+            }
+        }
+        if p.piece_type == Type::King {
+            self.castling_availability.set(p.color, true, false);
+            self.castling_availability.set(p.color, false, false);
+        }
+        // a move from or to a castling rook's square means it has moved or been captured
+        for &color in &[Color::White, Color::Black] {
+            for &kingside in &[true, false] {
+                let rook = self.castling_rook(color, kingside);
+                if start == rook || end == rook {
+                    self.castling_availability.set(color, kingside, false);
+                }
+            }
+        }
+        if m.is_en_passant() {
+            // the captured pawn is beside the start square, not on the end square
+            undo.captured = self.remove_piece(Square::new(end.file(), start.rank()));
+        }
+        self.en_passant_square = None;
+        if m.is_double_push() {
+            // the square the pawn skipped over
+            self.en_passant_square = Some(Square::new(end.file(), (start.rank() + end.rank()) / 2));
+        }
+        self.hash ^= zobrist::KEYS.castling(self.castling_availability.bits());
+        self.hash ^= zobrist::KEYS.en_passant(self.en_passant_target());
+
+        // the halfmove clock counts moves since the last capture or pawn move
+        if p.piece_type == Type::Pawn || undo.captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock = self.halfmove_clock.saturating_add(1);
+        }
+        if p.color == Color::Black {
+            self.fullmove_number = self.fullmove_number.saturating_add(1);
+        }
+        undo
+    }
+
+    /// takes back a move made with `make_move`, restoring the board
+    /// exactly as it was before the move
+    pub fn unmake_move(&mut self, m: Move, undo: Undo) {
+        let (start, end) = (m.from, m.to);
+        let p = match undo.moved {
+            Some(p) => p,
+            None => return
+        };
+        if m.is_castle() {
+            // put the king and rook back where they started
+            let (king_end, rook_end) = m.castling_squares();
+            self.remove_piece(king_end);
+            let rook = self.remove_piece(rook_end).unwrap();
+            self.set_piece(start, p);
+            self.set_piece(end, rook);
+        } else {
+            self.remove_piece(end);
+            self.set_piece(start, p);
+        }
+        if let Some(captured) = undo.captured {
+            if m.is_en_passant() {
+                self.set_piece(Square::new(end.file(), start.rank()), captured);
+            } else {
+                self.set_piece(end, captured);
+            }
+        }
+        self.active_color = p.color;
+        self.castling_availability = undo.castling_availability;
+        self.en_passant_square = undo.en_passant_square;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+        self.hash = undo.hash;
+    }
+
     pub fn print_board(&self) {
         for rank in (0..8).rev() {
             for file in 0..8 {
-                let piece = self.board[rank][file];
+                let piece = self.piece_at(Square::new(file, rank));
                 match piece {
                     None => print!(" - "),
                     Some(p) => {
@@ -302,7 +626,7 @@ impl Board {
                     }
                 }
             }
-            println!("");
+            println!();
         }
         match self.active_color {
             Color::White => println!("active color: White"),
@@ -321,14 +645,174 @@ impl Board {
         if self.castling_availability.black_queenside {
             print!("q");
         }
-        println!("");
+        println!();
         print!("en passant square: ");
         match self.en_passant_square {
             None => println!("None"),
-            Some(l) => println!("File: {}, Rank: {}", l.file, l.rank)
+            Some(l) => println!("File: {}, Rank: {}", l.file(), l.rank())
         }
         println!("halfmove clock: {}", self.halfmove_clock);
         println!("fullmove number: {}", self.fullmove_number);
-        println!("");
+        println!("fen: {}", self.to_fen());
+        println!();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use board::{self, Board, FenError};
+    use color::Color;
+    use logic;
+    use piece::{Piece, Type};
+    use square::Square;
+    use zobrist;
+
+    fn check_make_unmake(fen: &str, m: &str) {
+        let original = Board::from_fen(fen).unwrap();
+        let m = logic::parse_move(&original, m).unwrap();
+        let mut board = original;
+        let undo = board.make_move(m);
+        assert_eq!(board, original.after_move(m));
+        assert_eq!(board.hash, zobrist::hash(&board));
+        board.unmake_move(m, undo);
+        assert_eq!(board, original);
+    }
+
+    #[test]
+    fn test_make_unmake() {
+        // quiet move, capture, double push, en passant, castles and promotion
+        check_make_unmake("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "g1f3");
+        check_make_unmake("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2", "e4d5");
+        check_make_unmake("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "e2e4");
+        check_make_unmake("rnbqkbnr/p1p1p1pp/3p4/1p2Pp2/3P4/8/PPP2PPP/RNBQKBNR w KQkq f6 0 4", "e5f6");
+        check_make_unmake("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1");
+        check_make_unmake("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8c8");
+        check_make_unmake("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "a1a8");
+        check_make_unmake("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8");
+        // Chess960 castles, written as the king taking its rook
+        check_make_unmake("r5kr/8/8/8/8/8/8/RK5R w AHah - 0 1", "b1a1");
+        check_make_unmake("r5kr/8/8/8/8/8/8/RK5R w AHah - 0 1", "b1h1");
+        check_make_unmake("r5kr/8/8/8/8/8/8/RK5R b AHah - 0 1", "g8h8");
+    }
+
+    #[test]
+    fn test_mirror() {
+        let board = Board::from_fen("rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w Kq c6 0 2").unwrap();
+        let mirrored = board.mirror();
+        assert_eq!(mirrored.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/2P5/8/PP1PPPPP/RNBQKBNR b Qk c3 0 2");
+        assert_eq!(mirrored.hash, zobrist::hash(&mirrored));
+        assert_eq!(mirrored.mirror(), board);
+        let chess960 = Board::from_fen("r5kr/8/8/8/8/8/8/RK5R w Ah - 0 1").unwrap();
+        assert_eq!(chess960.mirror().to_fen(), "rk5r/8/8/8/8/8/8/R5KR b Kq - 0 1");
+    }
+
+    #[test]
+    fn test_chess960_castling() {
+        let mut board = Board::from_fen("r5kr/8/8/8/8/8/8/RK5R w AHah - 0 1").unwrap();
+        board.make_move(logic::parse_move(&board, "b1a1").unwrap());
+        assert_eq!(board.to_fen(), "r5kr/8/8/8/8/8/8/2KR3R b kq - 1 1");
+        board.make_move(logic::parse_move(&board, "g8h8").unwrap());
+        assert_eq!(board.to_fen(), "r4rk1/8/8/8/8/8/8/2KR3R w - - 2 2");
+    }
+
+    #[test]
+    fn test_chess960_fen() {
+        // Shredder-FEN comes back as X-FEN, which only names inner rooks
+        let board = Board::from_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9").unwrap();
+        assert_eq!(board.to_fen(), "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9");
+        assert_eq!(board.castling_rook(Color::White, false), Square::new(5, 0));
+        for fen in &["4k3/8/8/8/8/8/8/R2RK3 w D - 0 1", "rr2k3/8/8/8/8/8/8/4K3 b b - 0 1"] {
+            assert_eq!(Board::from_fen(fen).unwrap().to_fen(), *fen);
+        }
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/R2RK3 w A - 0 1").unwrap().to_fen(),
+                   "4k3/8/8/8/8/8/8/R2RK3 w Q - 0 1");
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w E - 0 1"),
+                   Err(FenError::Castling("E".to_owned())));
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/R2RK3 w QA - 0 1"),
+                   Err(FenError::Castling("QA".to_owned())));
+    }
+
+    #[test]
+    fn test_chess960_start_positions() {
+        assert_eq!(board::chess960_fen(518).unwrap(), board::START_FEN);
+        assert_eq!(board::chess960_fen(0).unwrap(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1");
+        assert_eq!(board::chess960_fen(960), None);
+        let fens = (0..960).map(|i| board::chess960_fen(i).unwrap()).collect::<HashSet<_>>();
+        assert_eq!(fens.len(), 960);
+        for fen in &fens {
+            assert_eq!(Board::from_fen(fen).unwrap().to_fen(), *fen);
+        }
+    }
+
+    #[test]
+    fn test_piece_lists() {
+        let mut board = Board::from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.king_square(Color::Black), Some(Square::new(4, 7)));
+        assert_eq!(board.count(Color::White, Type::Pawn), 1);
+        assert_eq!(board.pieces().count(), 4);
+        let m = logic::parse_move(&board, "a7b8r").unwrap();
+        let undo = board.make_move(m);
+        assert_eq!(board.count(Color::White, Type::Pawn), 0);
+        assert_eq!(board.count(Color::White, Type::Rook), 1);
+        assert_eq!(board.count(Color::Black, Type::Knight), 0);
+        assert_eq!(board.pieces().collect::<Vec<_>>(), vec![
+            (Square::new(1, 7), Piece { piece_type: Type::Rook, color: Color::White }),
+            (Square::new(4, 0), Piece { piece_type: Type::King, color: Color::White }),
+            (Square::new(4, 7), Piece { piece_type: Type::King, color: Color::Black })
+        ]);
+        board.unmake_move(m, undo);
+        assert_eq!(board.count(Color::Black, Type::Knight), 1);
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/4K3 w - - 0 1").unwrap().king_square(Color::Black), None);
+    }
+
+    #[test]
+    fn test_from_fen_four_fields() {
+        let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3").unwrap();
+        assert_eq!(board.active_color, Color::Black);
+        assert_eq!(board.en_passant_square, Some(Square::new(4, 2)));
+        assert_eq!(board.halfmove_clock, 0);
+        assert_eq!(board.fullmove_number, 1);
+    }
+
+    #[test]
+    fn test_to_fen_round_trip() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2",
+            "r1bqk2r/pppp1pbp/2n2np1/1B2p3/4P3/2P2N2/PP1P1PPP/RNBQ1RK1 w kq - 1 6",
+            "r3k2r/8/8/8/8/8/8/R3K2R b Qk - 12 40",
+            "8/5k2/8/8/8/8/2K5/8 w - - 300 210",
+            "2k4R/8/2K5/8/8/8/8/8 b - - 0 45",
+        ];
+        for fen in fens.iter() {
+            assert_eq!(Board::from_fen(fen).unwrap().to_fen(), *fen);
+        }
+    }
+
+    #[test]
+    fn test_from_fen_placement_errors() {
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8 w - - 0 1"), Err(FenError::RankCount(7)));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/7 w - - 0 1"), Err(FenError::RankLength(1)));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/ppppppppp w - - 0 1"), Err(FenError::RankLength(1)));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/7x w - - 0 1"), Err(FenError::UnknownPiece('x')));
+    }
+
+    #[test]
+    fn test_from_fen_field_errors() {
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8 w -"), Err(FenError::FieldCount(3)));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8 x - - 0 1"),
+                   Err(FenError::SideToMove("x".to_owned())));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8 w KK - 0 1"),
+                   Err(FenError::Castling("KK".to_owned())));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8 w - e4 0 1"),
+                   Err(FenError::EnPassant("e4".to_owned())));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8 w - - x 1"),
+                   Err(FenError::HalfmoveClock("x".to_owned())));
+        // the halfmove clock can pass 255 in a legal game
+        assert_eq!(Board::from_fen("8/5k2/8/8/8/8/2K5/8 w - - 300 210").unwrap().halfmove_clock, 300);
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8 w - - 0 -1"),
+                   Err(FenError::FullmoveNumber("-1".to_owned())));
     }
 }
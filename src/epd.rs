@@ -0,0 +1,216 @@
+use std::error::Error;
+use std::f64;
+use std::fmt;
+use std::str::FromStr;
+
+use board::{Board, FenError};
+use moves::Move;
+use san::SanError;
+
+/// the reasons a line can fail to parse as an EPD record
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum EpdError {
+    /// the position fields were not a valid FEN without its clocks
+    Fen(FenError),
+    /// a move in a bm or am operation could not be played
+    Move(SanError),
+    /// an operation had a missing or malformed operand, or no closing ';'
+    Operation(String)
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EpdError::Fen(ref e) => write!(f, "{}", e),
+            EpdError::Move(ref e) => write!(f, "{}", e),
+            EpdError::Operation(ref s) => write!(f, "invalid operation '{}'", s)
+        }
+    }
+}
+
+impl Error for EpdError {}
+
+/// An EPD record: a position and the operations describing it, most often
+/// the moves a test suite expects to be found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Epd {
+    pub board: Board,
+    /// the best moves (bm); finding any one of them solves the position
+    pub best_moves: Vec<Move>,
+    /// the moves to avoid (am)
+    pub avoid_moves: Vec<Move>,
+    pub id: Option<String>,
+    /// the primary comment (c0)
+    pub comment: Option<String>,
+    /// the depth the position was analysed to (acd)
+    pub analysis_depth: Option<u8>,
+    /// the side to move mates in this many moves (dm)
+    pub direct_mate: Option<u32>,
+    /// every operation in the order given, including the ones above, with
+    /// quotes taken off string operands
+    pub operations: Vec<(String, Vec<String>)>
+}
+
+impl Epd {
+    /// the operands of the first operation with the given opcode
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter().find(|op| op.0 == opcode).map(|op| op.1.as_slice())
+    }
+
+    /// returns true if a search that chose the move with the given score
+    /// solved the position: it played a best move, avoided every move to
+    /// avoid and, for a direct mate, saw the mate
+    pub fn is_solved_by(&self, m: Move, score: f64) -> bool {
+        (self.best_moves.is_empty() || self.best_moves.contains(&m)) &&
+            !self.avoid_moves.contains(&m) &&
+            (self.direct_mate.is_none() || score == f64::INFINITY)
+    }
+}
+
+/// splits the operations into opcodes and operands, keeping quoted strings
+/// whole
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, EpdError> {
+    let mut operations = Vec::new();
+    let mut words = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            ';' => {
+                if words.is_empty() {
+                    return Err(EpdError::Operation(text.to_owned()));
+                }
+                let opcode = words.remove(0);
+                operations.push((opcode, words.split_off(0)));
+            },
+            '"' => {
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(ch) => word.push(ch),
+                        None => return Err(EpdError::Operation(text.to_owned()))
+                    }
+                }
+                words.push(word);
+            },
+            _ if ch.is_whitespace() => {},
+            _ => {
+                let mut word = ch.to_string();
+                while let Some(&ch) = chars.peek() {
+                    if ch.is_whitespace() || ch == ';' || ch == '"' {
+                        break;
+                    }
+                    word.push(ch);
+                    chars.next();
+                }
+                words.push(word);
+            }
+        }
+    }
+    // every operation ends with a semicolon
+    if !words.is_empty() {
+        return Err(EpdError::Operation(words.join(" ")));
+    }
+    Ok(operations)
+}
+
+/// the only operand of an operation
+fn single(opcode: &str, operands: &[String]) -> Result<String, EpdError> {
+    match operands {
+        [operand] => Ok(operand.clone()),
+        _ => Err(EpdError::Operation(format!("{} {}", opcode, operands.join(" "))))
+    }
+}
+
+fn number<T: FromStr>(opcode: &str, operands: &[String]) -> Result<T, EpdError> {
+    let operand = single(opcode, operands)?;
+    operand.parse().map_err(|_| EpdError::Operation(format!("{} {}", opcode, operand)))
+}
+
+/// parses a record such as
+/// `r1b1k2r/... w kq - bm Nxe5; id "WAC.002";`
+impl FromStr for Epd {
+    type Err = EpdError;
+
+    fn from_str(s: &str) -> Result<Epd, EpdError> {
+        let fields = s.split_whitespace().take(4).collect::<Vec<_>>();
+        let board = Board::from_fen(&fields.join(" ")).map_err(EpdError::Fen)?;
+        // the operations are whatever follows the fourth field
+        let mut rest = s.trim_start();
+        for _ in 0..4 {
+            rest = rest.find(char::is_whitespace).map_or("", |i| rest[i..].trim_start());
+        }
+        let operations = parse_operations(rest)?;
+
+        let mut epd = Epd {
+            board,
+            best_moves: Vec::new(),
+            avoid_moves: Vec::new(),
+            id: None,
+            comment: None,
+            analysis_depth: None,
+            direct_mate: None,
+            operations: Vec::new()
+        };
+        let moves = |operands: &[String]| operands.iter()
+            .map(|san| board.parse_san(san).map_err(EpdError::Move))
+            .collect::<Result<Vec<_>, _>>();
+        for (opcode, operands) in &operations {
+            match opcode.as_str() {
+                "bm" => epd.best_moves = moves(operands)?,
+                "am" => epd.avoid_moves = moves(operands)?,
+                "id" => epd.id = Some(single(opcode, operands)?),
+                "c0" => epd.comment = Some(single(opcode, operands)?),
+                "acd" => epd.analysis_depth = Some(number(opcode, operands)?),
+                "dm" => epd.direct_mate = Some(number(opcode, operands)?),
+                _ => {}
+            }
+        }
+        epd.operations = operations;
+        Ok(epd)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::f64;
+
+    use epd::{Epd, EpdError};
+    use san::SanError;
+
+    #[test]
+    fn test_parse_epd() {
+        let epd = "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\"; c0 \"a \
+                   sacrifice; mate follows\"; acd 12; dm 3; ce +M3;".parse::<Epd>().unwrap();
+        assert_eq!(epd.board.to_fen(), "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1");
+        assert_eq!(epd.best_moves.iter().map(|m| m.to_string()).collect::<Vec<_>>(), vec!["g3g6"]);
+        assert_eq!(epd.id.as_deref(), Some("WAC.001"));
+        assert_eq!(epd.comment.as_deref(), Some("a sacrifice; mate follows"));
+        assert_eq!(epd.analysis_depth, Some(12));
+        assert_eq!(epd.direct_mate, Some(3));
+        assert_eq!(epd.operation("ce"), Some(&["+M3".to_owned()][..]));
+        assert_eq!(epd.operation("pv"), None);
+        assert!(epd.is_solved_by(epd.best_moves[0], f64::INFINITY));
+        assert!(!epd.is_solved_by(epd.best_moves[0], 3.0));
+    }
+
+    #[test]
+    fn test_parse_epd_avoid_moves() {
+        let epd = "4k3/8/8/8/8/8/4P3/4K3 w - - am Kd1 Kf1; id \"x\";".parse::<Epd>().unwrap();
+        assert_eq!(epd.avoid_moves.len(), 2);
+        assert!(epd.best_moves.is_empty());
+        assert!(!epd.is_solved_by(epd.avoid_moves[1], 0.0));
+        assert_eq!("4k3/8/8/8/8/8/4P3/4K3 w - -".parse::<Epd>().unwrap().operations, vec![]);
+    }
+
+    #[test]
+    fn test_parse_epd_errors() {
+        assert_eq!("4k3/8/8/8/8/8/4P3/4K3 w - - bm Ke3;".parse::<Epd>(),
+                   Err(EpdError::Move(SanError::Illegal("Ke3".to_owned()))));
+        assert_eq!("4k3/8/8/8/8/8/4P3/4K3 w - - bm e4".parse::<Epd>(),
+                   Err(EpdError::Operation("bm e4".to_owned())));
+        assert_eq!("4k3/8/8/8/8/8/4P3/4K3 w - - acd x;".parse::<Epd>(),
+                   Err(EpdError::Operation("acd x".to_owned())));
+        assert!("4k3/8/8/8/8/8/4P3 w - - bm e4;".parse::<Epd>().is_err());
+    }
+}
@@ -1,12 +1,14 @@
-use std::cell::RefMut;
 use std::collections::HashMap;
 use std::f64;
+use std::time::{Duration, Instant};
 
-use board::{Board, Location};
+use bitboard;
+use board::Board;
 use color::Color;
-use logic;
-use piece::{Piece, Type};
-use zobrist::{Entry, Table};
+use movegen::{self, MoveList, MAX_MOVES};
+use moves::Move;
+use piece::Type;
+use zobrist::Entry;
 
 // the following tables are taken from
 // https://chessprogramming.wikispaces.com/Simplified+evaluation+function
@@ -75,88 +77,37 @@ const MOBILITY_WEIGHT: f64 = 0.1f64;
 // const BAD_PAWN_STRUCT_WEIGHT: f64 = -0.5f64;
 
 pub fn evaluate_position(board: &Board) -> f64 {
-    let mut king_diff: f64 = 0.0;
-    let mut queen_diff: f64 = 0.0;
-    let mut rook_diff: f64 = 0.0;
-    let mut knight_diff: f64 = 0.0;
-    let mut bishop_diff: f64 = 0.0;
-    let mut pawn_diff: f64 = 0.0;
-    let mut mobility_diff: f64 = 0.0;
     let mut output = 0.0;
-
-    for rank in 0..8 {
-        for file in 0..8 {
-            if let Some(p) = board.board[rank][file] {
-                match p.piece_type {
-                    Type::Pawn => {
-                        if p.color == Color::White {
-                            pawn_diff += 1.0;
-                            output += PAWN_TABLE[7 - rank][file];
-                        } else {
-                            pawn_diff -= 1.0;
-                            output -= PAWN_TABLE[rank][file];
-                        }
-                    },
-                    Type::Bishop => {
-                        if p.color == Color::White {
-                            bishop_diff += 1.0;
-                            output += BISHOP_TABLE[7 - rank][file];
-                        } else {
-                            bishop_diff -= 1.0;
-                            output -= BISHOP_TABLE[rank][file];
-                        }
-                    },
-                    Type::Knight => {
-                        if p.color == Color::White {
-                            knight_diff += 1.0;
-                            output += KNIGHT_TABLE[7 - rank][file];
-                        } else {
-                            knight_diff -= 1.0;
-                            output -= KNIGHT_TABLE[rank][file];
-                        }
-                    },
-                    Type::Rook => {
-                        if p.color == Color::White {
-                            rook_diff += 1.0;
-                            output += ROOK_TABLE[7 - rank][file];
-                        } else {
-                            rook_diff -= 1.0;
-                            output -= ROOK_TABLE[rank][file];
-                        }
-                    },
-                    Type::Queen => {
-                        if p.color == Color::White {
-                            queen_diff += 1.0;
-                            output += QUEEN_TABLE[7 - rank][file];
-                        } else {
-                            queen_diff -= 1.0;
-                            output -= QUEEN_TABLE[rank][file];
-                        }
-                    },
-                    Type::King => {
-                        king_diff += if p.color == Color::White { 1.0 } else { -1.0 };
-                    },
-                }
-                let moves = logic::get_legal_moves(
-                    &board, Location { rank: rank as u8, file: file as u8 });
-                if p.color == Color::White {
-                    mobility_diff += moves.len() as f64;
-                } else {
-                    mobility_diff -= moves.len() as f64;
-                }
-            }
+    for (square, piece) in board.pieces() {
+        let table = match piece.piece_type {
+            Type::Pawn => &PAWN_TABLE,
+            Type::Bishop => &BISHOP_TABLE,
+            Type::Knight => &KNIGHT_TABLE,
+            Type::Rook => &ROOK_TABLE,
+            Type::Queen => &QUEEN_TABLE,
+            Type::King => continue
+        };
+        let rank = square.rank() as usize;
+        let file = square.file() as usize;
+        if piece.color == Color::White {
+            output += table[7 - rank][file];
+        } else {
+            output -= table[rank][file];
         }
     }
 
+    let diff = |piece_type| {
+        board.count(Color::White, piece_type) as f64 - board.count(Color::Black, piece_type) as f64
+    };
+    let king_diff = diff(Type::King);
     let king_weight = KING_WEIGHT * king_diff;
-    let queen_weight = QUEEN_WEIGHT * queen_diff;
-    let rook_weight = ROOK_WEIGHT * rook_diff;
-    let knight_weight = KNIGHT_WEIGHT * knight_diff;
-    let bishop_weight = BISHOP_WEIGHT * bishop_diff;
-    let pawn_weight = PAWN_WEIGHT * pawn_diff;
-    let mobility_weight = MOBILITY_WEIGHT * mobility_diff;
+    let queen_weight = QUEEN_WEIGHT * diff(Type::Queen);
+    let rook_weight = ROOK_WEIGHT * diff(Type::Rook);
+    let knight_weight = KNIGHT_WEIGHT * diff(Type::Knight);
+    let bishop_weight = BISHOP_WEIGHT * diff(Type::Bishop);
+    let pawn_weight = PAWN_WEIGHT * diff(Type::Pawn);
     output += king_weight + queen_weight + rook_weight + knight_weight + 
-        bishop_weight + pawn_weight;// + mobility_weight;
+        bishop_weight + pawn_weight;// + mobility(board);
     // if there is no king, then return the worst possible score (for mate)
     if king_diff > 0.0 {
         output = f64::INFINITY;
@@ -169,95 +120,218 @@ pub fn evaluate_position(board: &Board) -> f64 {
     output
 }
 
+/// the mobility term from white's point of view: the difference in the
+/// number of legal moves each side would have if it were to move. Not yet
+/// part of `evaluate_position`.
+pub fn mobility(board: &Board) -> f64 {
+    let mobility = movegen::mobility(board);
+    MOBILITY_WEIGHT * (mobility[Color::White as usize] as f64 - mobility[Color::Black as usize] as f64)
+}
+
+/// returns true if the position and its mirror image, with the colors
+/// swapped, evaluate the same for the side to move
+pub fn is_symmetric(board: &Board) -> bool {
+    let (score, mirrored) = (evaluate_position(board), evaluate_position(&board.mirror()));
+    score == mirrored || (score - mirrored).abs() < 1e-9
+}
+
+/// when an iterative deepening search stops
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Limit {
+    /// search to exactly this depth
+    Depth(u8),
+    /// stop searching once this much time has passed
+    MoveTime(Duration)
+}
+
+impl Limit {
+    /// the limit given to a UCI go command as "depth <plies>" or
+    /// "movetime <ms>", or a search to depth 4 if it has neither
+    pub fn from_go(input: &[&str]) -> Limit {
+        let mut limit = Limit::Depth(4);
+        for pair in input.windows(2) {
+            match pair[0] {
+                "depth" => if let Ok(depth) = pair[1].parse::<u8>() {
+                    limit = Limit::Depth(depth);
+                },
+                "movetime" => if let Ok(ms) = pair[1].parse::<u64>() {
+                    limit = Limit::MoveTime(Duration::from_millis(ms));
+                },
+                _ => ()
+            }
+        }
+        limit
+    }
+}
+
+/// the deepest `search` goes however much time it has
+const MAX_DEPTH: u8 = 64;
+
+/// how many nodes a search visits between looks at the clock
+const NODES_PER_CHECK: u32 = 4096;
+
+/// The time a search has to give up by. Reading the clock isn't free, so it
+/// is only checked every few thousand nodes.
+pub struct Deadline {
+    at: Option<Instant>,
+    nodes: u32,
+    passed: bool
+}
+
+impl Deadline {
+    /// a deadline that never passes
+    pub fn none() -> Deadline {
+        Deadline { at: None, nodes: 0, passed: false }
+    }
+
+    pub fn at(at: Instant) -> Deadline {
+        Deadline { at: Some(at), nodes: 0, passed: false }
+    }
+
+    pub fn has_passed(&self) -> bool {
+        self.passed
+    }
+
+    /// counts a node, and returns true once the deadline has passed
+    fn check(&mut self) -> bool {
+        self.nodes = self.nodes.wrapping_add(1);
+        if !self.passed && self.nodes.is_multiple_of(NODES_PER_CHECK) {
+            self.passed = self.at.is_some_and(|at| Instant::now() >= at);
+        }
+        self.passed
+    }
+}
+
+/// searches one ply deeper each time until the limit is reached or a mate
+/// is found, returning the best line and its score for the side to move.
+/// `report` is given the depth, line and score of each finished iteration.
+pub fn search(board: &mut Board, limit: Limit, table: &mut HashMap<u64, Entry>,
+              history: &mut Vec<u64>, report: &mut dyn FnMut(u8, &[Move], f64)) -> (Vec<Move>, f64) {
+    let start = Instant::now();
+    let mut deadline = match limit {
+        Limit::Depth(_) => Deadline::none(),
+        Limit::MoveTime(time) => Deadline::at(start + time)
+    };
+    let mut best = (Vec::new(), 0.0);
+    for depth in 1..=MAX_DEPTH {
+        let mut line = Vec::new();
+        // the first iteration always finishes, so there is a move to return
+        let mut unlimited = Deadline::none();
+        let iteration_deadline = if depth == 1 { &mut unlimited } else { &mut deadline };
+        let score = pvs(board, f64::NEG_INFINITY, f64::INFINITY, depth, &mut line, table, history,
+                        iteration_deadline);
+        // an iteration cut short is thrown away for the last one that finished
+        if deadline.has_passed() {
+            break;
+        }
+        report(depth, &line, score);
+        best = (line, score);
+        let done = match limit {
+            Limit::Depth(max) => depth >= max,
+            Limit::MoveTime(time) => start.elapsed() >= time
+        };
+        if done || score.is_infinite() || best.0.is_empty() {
+            break;
+        }
+    }
+    best
+}
+
 /// uses principle variation search to return the minimax
-/// of the given position
-pub fn pvs(board: &Board, mut alpha: f64, beta: f64, depth: u8, line: &mut Vec<String>, 
-           table: &mut HashMap<u64, Entry>, zobrist: &Table) -> f64 {
+/// of the given position. `history` holds the hashes of the positions
+/// before this one, and going back to any of them is scored as a draw.
+/// Once the deadline passes the search unwinds and its result means nothing.
+/// `table` is not written to until the transposition table is bounded.
+#[allow(clippy::too_many_arguments, clippy::only_used_in_recursion)]
+pub fn pvs(board: &mut Board, mut alpha: f64, beta: f64, depth: u8, line: &mut Vec<Move>,
+           table: &mut HashMap<u64, Entry>, history: &mut Vec<u64>, deadline: &mut Deadline) -> f64 {
+    if deadline.check() {
+        return 0.0
+    }
     if depth == 0 {
-        return evaluate_position(board)
+        return quiescence(board, alpha, beta, deadline)
     }
-    for rank in 0..8 {
-        for file in 0..8 {
-            if let Some(p) = board.board[rank][file] {
-                if p.color == board.active_color {
-                    let legal_moves = logic::get_legal_moves(
-                        board, Location { rank: rank as u8, file: file as u8 });
-                    for i in 0..legal_moves.len() {
-                        if let Some(move_loc) = legal_moves.get(i) {
-                            let mut newline = Vec::new();
-                            let original_loc = Location { rank: rank as u8, file: file as u8 };
-                            let new_board = board.after_move(original_loc, *move_loc);
-                            let score = -pvs(&new_board, -beta, -alpha, depth - 1, &mut newline, table, zobrist);
-                            // let hash = zobrist.hash(&new_board);
-                            // let mut score = 0.0;
-                            // if table.contains_key(&hash) {
-                            //     let e_depth = table.get(&hash).unwrap().depth;
-                            //     let e_eval = table.get(&hash).unwrap().evaluation;
-                            //     if e_depth >= depth - 1 {
-                            //         score = e_eval;
-                            //         newline = table.get(&hash).unwrap().line.clone();
-                            //     } else {
-                            //         score = -pvs(&new_board, -beta, -alpha, depth - 1, &mut newline, table, zobrist);
-                            //         table.insert(hash, Entry { best_move: (original_loc, move_loc.clone()),
-                            //         depth: depth - 1, evaluation: score, line: line.clone() });
-                            //     }
-                            // } else {
-                            //     score = -pvs(&new_board, -beta, -alpha, depth - 1, &mut newline, table, zobrist);
-                            //     table.insert(hash, Entry { best_move: (original_loc, move_loc.clone()),
-                            //     depth: depth - 1, evaluation: score, line: line.clone() });
-                            // }
-                            
-                            // for checkmate
-                            if score.is_infinite() {
-                                if board.active_color == Color::White && score > 0.0 {
-                                    line.clear();
-                                    line.push(original_loc.to_notation());
-                                    line.push(move_loc.to_notation());
-                                    line.push(" ".to_owned());
-                                    for m in &newline {
-                                        line.push(m.clone());
-                                    }
-                                    return score;
-                                } else if board.active_color == Color::Black && score > 0.0 {
-                                    line.clear();
-                                    line.push(original_loc.to_notation());
-                                    line.push(move_loc.to_notation());
-                                    line.push(" ".to_owned());
-                                    for m in &newline {
-                                        line.push(m.clone());
-                                    }
-                                    return score;
-                                }
-                            }
-                            if score >= beta {
-                                return beta
-                            }
-                            if score > alpha {
-                                alpha = score;
-                                line.clear();
-                                line.push(original_loc.to_notation());
-                                line.push(move_loc.to_notation());
-                                line.push(" ".to_owned());
-                                for m in &newline {
-                                    line.push(m.clone());
-                                }
-                                // let e_depth = table.get(&hash).unwrap().depth;
-                                // let e_eval = table.get(&hash).unwrap().evaluation;
-                                // table.insert(hash, Entry { best_move: (original_loc, move_loc.clone()),
-                                // depth: e_depth, evaluation: e_eval, line: line.clone() });
-                            }
-                        }
-                    }
-                }
-            }
+    let mut moves = MoveList::new();
+    board.generate_moves(&mut moves);
+    order_moves(board, &mut moves);
+    if moves.is_empty() {
+        // checkmate is the worst possible score, stalemate is a draw
+        return if board.checkers() != bitboard::EMPTY { f64::NEG_INFINITY } else { 0.0 }
+    }
+    for &m in moves.iter() {
+        let mut newline = Vec::new();
+        history.push(board.hash);
+        let undo = board.make_move(m);
+        let score = if is_repetition(board, history) {
+            0.0
+        } else {
+            -pvs(board, -beta, -alpha, depth - 1, &mut newline, table, history, deadline)
+        };
+        board.unmake_move(m, undo);
+        history.pop();
+
+        // for checkmate
+        if score.is_infinite() && score > 0.0 {
+            set_line(line, m, &newline);
+            return score;
+        }
+        if score >= beta {
+            return beta
+        }
+        if score > alpha {
+            alpha = score;
+            set_line(line, m, &newline);
+            // table.insert(board.hash, Entry { best_move: m, depth, evaluation: score,
+            //     line: line.clone() });
         }
     }
     alpha
 }
 
+/// returns true if the position came up before. Only the positions since
+/// the last capture or pawn move need checking, since none before it can
+/// come back.
+fn is_repetition(board: &Board, history: &[u64]) -> bool {
+    history.iter().rev().take(board.halfmove_clock as usize).any(|&hash| hash == board.hash)
+}
+
+/// puts the captures that win material first and the ones that lose it
+/// last, with the quiet moves in between
+fn order_moves(board: &Board, moves: &mut MoveList) {
+    let mut scores = [0; MAX_MOVES];
+    for (score, &m) in scores.iter_mut().zip(moves.iter()) {
+        if m.is_capture() || m.promotion.is_some() {
+            let see = board.see(m);
+            *score = if see >= 0 { -see - 1 } else { -see };
+        }
+    }
+    // an insertion sort, so the quiet moves keep the order they came in
+    for i in 1..moves.len() {
+        let (m, score) = (moves[i], scores[i]);
+        let mut j = i;
+        while j > 0 && scores[j - 1] > score {
+            moves[j] = moves[j - 1];
+            scores[j] = scores[j - 1];
+            j -= 1;
+        }
+        moves[j] = m;
+        scores[j] = score;
+    }
+}
+
+/// replaces the line with the given move followed by the rest of the line
+fn set_line(line: &mut Vec<Move>, m: Move, rest: &[Move]) {
+    line.clear();
+    line.push(m);
+    line.extend_from_slice(rest);
+}
+
 /// performs a quiescence search on the given position
 /// used to evaluate 'quiet' positions
-fn quiescence(board: &Board, mut alpha: f64, beta: f64) -> f64 {
+pub fn quiescence(board: &mut Board, mut alpha: f64, beta: f64, deadline: &mut Deadline) -> f64 {
+    if deadline.check() {
+        return 0.0
+    }
     let evaluation = evaluate_position(board);
     if evaluation >= beta {
         return beta
@@ -267,33 +341,130 @@ fn quiescence(board: &Board, mut alpha: f64, beta: f64) -> f64 {
     }
 
     // examime every capture
-    for rank in 0..8 {
-        for file in 0..8 {
-            if let Some(p) = board.board[rank][file] {
-                if p.color == board.active_color {
-                    let legal_moves = logic::get_legal_moves(
-                        board, Location { rank: rank as u8, file: file as u8 });
-                    for i in 0..legal_moves.len() {
-                        if let Some(move_loc) = legal_moves.get(i) {
-                            if let Some(other_p) = 
-                                board.board[move_loc.rank as usize][move_loc.file as usize] {
-                                    if p.color != other_p.color {
-                                        let original_loc = Location { rank: rank as u8, file: file as u8 };
-                                        let new_board = board.after_move(original_loc, *move_loc);
-                                        let score = -quiescence(&new_board, -beta, -alpha);
-                                        if score >= beta {
-                                            return beta
-                                        }
-                                        if score > alpha {
-                                            alpha = score;
-                                        }
-                                    }
-                                }
-                        }
-                    }
-                }
-            }
+    let mut moves = MoveList::new();
+    board.generate_captures(&mut moves);
+    for &m in moves.iter() {
+        // a capture that loses material can't raise the score above standing pat
+        if !board.see_ge(m, 0) {
+            continue;
+        }
+        let undo = board.make_move(m);
+        let score = -quiescence(board, -beta, -alpha, deadline);
+        board.unmake_move(m, undo);
+        if score >= beta {
+            return beta
+        }
+        if score > alpha {
+            alpha = score;
         }
     }
     alpha
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::f64;
+    use std::time::{Duration, Instant};
+
+    use board::Board;
+    use evaluation::{self, Deadline, Limit};
+    use logic;
+
+    #[test]
+    fn test_evaluation_is_symmetric() {
+        for fen in &["rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                     "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                     "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2",
+                     "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 0 1"] {
+            assert!(evaluation::is_symmetric(&Board::from_fen(fen).unwrap()), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_quiescence_misses_capture_against_pinned_defender() {
+        // Qxd6 wins a knight, since the e7 pawn is pinned, but see counts
+        // the pawn as a defender and quiescence skips the capture. This is
+        // a known limitation of see, not the play we want.
+        let mut board = Board::from_fen("4k3/4p3/3n4/8/8/8/8/3QR2K w - - 0 1").unwrap();
+        let capture = logic::parse_move(&board, "d1d6").unwrap();
+        assert!(board.see(capture) < 0);
+        let stand_pat = evaluation::evaluate_position(&board);
+        let mut after = board;
+        after.make_move(capture);
+        assert!(-evaluation::evaluate_position(&after) > stand_pat);
+        assert_eq!(evaluation::quiescence(&mut board, f64::NEG_INFINITY, f64::INFINITY,
+                                          &mut Deadline::none()), stand_pat);
+    }
+
+    #[test]
+    fn test_mobility() {
+        let start = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert_eq!(evaluation::mobility(&start), 0.0);
+        let e4 = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+        // 30 moves for white against black's 20
+        assert!((evaluation::mobility(&e4) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_limit_from_go() {
+        assert_eq!(Limit::from_go(&["go"]), Limit::Depth(4));
+        assert_eq!(Limit::from_go(&["go", "depth", "7"]), Limit::Depth(7));
+        assert_eq!(Limit::from_go(&["go", "movetime", "1500"]), Limit::MoveTime(Duration::from_millis(1500)));
+        assert_eq!(Limit::from_go(&["go", "wtime", "1000", "depth", "x"]), Limit::Depth(4));
+    }
+
+    #[test]
+    fn test_search_limits() {
+        let mut board = Board::from_fen("6nb/5Ppk/6pp/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let (line, score) = evaluation::search(&mut board, Limit::Depth(4), &mut HashMap::new(), &mut Vec::new(),
+                                               &mut |_, _, _| ());
+        assert_eq!(score, f64::INFINITY);
+        assert_eq!(line[0].to_string(), "f7f8n");
+        let start = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let mut board = start;
+        let mut depths = Vec::new();
+        evaluation::search(&mut board, Limit::Depth(3), &mut HashMap::new(), &mut Vec::new(),
+                           &mut |depth, line, _| depths.push((depth, line.len())));
+        assert_eq!(depths, vec![(1, 1), (2, 2), (3, 3)]);
+        let (line, _) = evaluation::search(&mut board, Limit::MoveTime(Duration::from_millis(1)),
+                                           &mut HashMap::new(), &mut Vec::new(), &mut |_, _, _| ());
+        assert!(!line.is_empty());
+        assert_eq!(board, start);
+    }
+
+    #[test]
+    fn test_search_stops_at_movetime() {
+        let mut board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let start = Instant::now();
+        let (line, _) = evaluation::search(&mut board, Limit::MoveTime(Duration::from_millis(200)),
+                                           &mut HashMap::new(), &mut Vec::new(), &mut |_, _, _| ());
+        assert!(!line.is_empty());
+        assert!(start.elapsed() < Duration::from_millis(600), "took {:?}", start.elapsed());
+    }
+
+    #[test]
+    fn test_pvs_finds_knight_promotion_mate() {
+        let mut board = Board::from_fen("6nb/5Ppk/6pp/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let mut line = Vec::new();
+        let score = evaluation::pvs(&mut board, f64::NEG_INFINITY, f64::INFINITY, 2,
+                                    &mut line, &mut HashMap::new(), &mut Vec::new(), &mut Deadline::none());
+        assert_eq!(score, f64::INFINITY);
+        assert_eq!(line[0].to_string(), "f7f8n");
+    }
+
+    #[test]
+    fn test_pvs_scores_repetition_as_draw() {
+        // a queen down, white is happy to repeat by going back to h1
+        let mut board = Board::from_fen("7k/8/8/8/8/8/q7/6K1 w - - 10 40").unwrap();
+        let repeated = board.after_move(logic::parse_move(&board, "g1h1").unwrap()).hash;
+        let mut line = Vec::new();
+        let score = evaluation::pvs(&mut board, f64::NEG_INFINITY, f64::INFINITY, 1,
+                                    &mut line, &mut HashMap::new(), &mut Vec::new(), &mut Deadline::none());
+        assert!(score < -5.0);
+        let score = evaluation::pvs(&mut board, f64::NEG_INFINITY, f64::INFINITY, 1,
+                                    &mut line, &mut HashMap::new(), &mut vec![repeated, 1], &mut Deadline::none());
+        assert_eq!(score, 0.0);
+        assert_eq!(line[0].to_string(), "g1h1");
+    }
+}
@@ -0,0 +1,154 @@
+use std::iter;
+
+use board::{self, Board};
+use moves::Move;
+
+/// A move in a game tree, along with its annotations and the moves that
+/// can follow it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub m: Move,
+    /// numeric annotation glyphs, such as 1 for "!" or 4 for "??"
+    pub nags: Vec<u8>,
+    /// a comment before the move, only used at the start of a variation
+    pub comment_before: Option<String>,
+    /// a comment after the move
+    pub comment: Option<String>,
+    /// the replies to this move: the first continues the line, the others
+    /// are variations on it
+    pub children: Vec<Node>
+}
+
+impl Node {
+    pub fn new(m: Move) -> Node {
+        Node { m, nags: Vec::new(), comment_before: None, comment: None, children: Vec::new() }
+    }
+}
+
+/// A game: its headers, the position it started from and a tree of the
+/// moves played and considered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Game {
+    /// the tag pairs in the order they were given
+    pub headers: Vec<(String, String)>,
+    /// the standard start position unless the game was set up from a FEN
+    pub start: Board,
+    /// the first moves: the one played, then any variations on it
+    pub moves: Vec<Node>,
+    /// "1-0", "0-1", "1/2-1/2" or "*", if the game has one
+    pub result: Option<String>
+}
+
+impl Default for Game {
+    fn default() -> Game {
+        Game::new()
+    }
+}
+
+impl Game {
+    /// a game with no moves from the standard start position
+    pub fn new() -> Game {
+        Game::from_board(Board::from_fen(board::START_FEN).unwrap())
+    }
+
+    /// a game starting from the given position, with the SetUp and FEN
+    /// headers set if that isn't the standard start position
+    pub fn from_board(start: Board) -> Game {
+        let mut game = Game { headers: Vec::new(), start, moves: Vec::new(), result: None };
+        let fen = start.to_fen();
+        if fen != board::START_FEN {
+            game.set_header("SetUp", "1");
+            game.set_header("FEN", &fen);
+        }
+        game
+    }
+
+    /// the value of the first header with the given name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|header| header.0 == name).map(|header| header.1.as_str())
+    }
+
+    /// replaces the value of the header, or adds it at the end
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        match self.headers.iter_mut().find(|header| header.0 == name) {
+            Some(header) => header.1 = value.to_owned(),
+            None => self.headers.push((name.to_owned(), value.to_owned()))
+        }
+    }
+
+    /// the moves of the main line, following the first child at each step
+    pub fn main_line(&self) -> Vec<Move> {
+        let mut line = Vec::new();
+        let mut nodes = &self.moves;
+        while let Some(node) = nodes.first() {
+            line.push(node.m);
+            nodes = &node.children;
+        }
+        line
+    }
+
+    /// the start position followed by the position after each move of the
+    /// main line
+    pub fn positions(&self) -> impl Iterator<Item=Board> {
+        let start = self.start;
+        iter::once(start).chain(self.main_line().into_iter().scan(start, |board, m| {
+            board.make_move(m);
+            Some(*board)
+        }))
+    }
+
+    /// plays the move, which must be legal, at the end of the main line
+    pub fn push(&mut self, m: Move) {
+        let mut nodes = &mut self.moves;
+        while !nodes.is_empty() {
+            nodes = &mut nodes[0].children;
+        }
+        nodes.push(Node::new(m));
+    }
+
+    /// the list of moves that follow the node at the given path of child
+    /// indices, or the first moves for an empty path
+    pub fn children_mut(&mut self, path: &[usize]) -> &mut Vec<Node> {
+        let mut nodes = &mut self.moves;
+        for &i in path {
+            nodes = &mut nodes[i].children;
+        }
+        nodes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use board::Board;
+    use game::{Game, Node};
+    use logic;
+
+    #[test]
+    fn test_game_tree() {
+        let mut game = Game::new();
+        assert_eq!(game.header("FEN"), None);
+        let mut board = game.start;
+        for m in &["e2e4", "e7e5", "g1f3"] {
+            let m = logic::parse_move(&board, m).unwrap();
+            board.make_move(m);
+            game.push(m);
+        }
+        // 1... c5 as a variation on 1... e5
+        let sicilian = logic::parse_move(&game.positions().nth(1).unwrap(), "c7c5").unwrap();
+        game.children_mut(&[0]).push(Node::new(sicilian));
+        assert_eq!(game.main_line().len(), 3);
+        assert_eq!(game.moves[0].children.len(), 2);
+        assert_eq!(game.positions().last(), Some(board));
+        game.set_header("White", "ruci");
+        game.set_header("White", "ruci 2");
+        assert_eq!(game.headers, vec![("White".to_owned(), "ruci 2".to_owned())]);
+    }
+
+    #[test]
+    fn test_game_from_board() {
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1";
+        let game = Game::from_board(Board::from_fen(fen).unwrap());
+        assert_eq!(game.header("SetUp"), Some("1"));
+        assert_eq!(game.header("FEN"), Some(fen));
+    }
+}
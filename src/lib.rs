@@ -0,0 +1,20 @@
+pub mod attacks;
+pub mod bitboard;
+pub mod board;
+pub mod color;
+pub mod epd;
+pub mod evaluation;
+pub mod game;
+pub mod logic;
+pub mod magic;
+pub mod movegen;
+pub mod moves;
+pub mod outcome;
+pub mod perft;
+pub mod pgn;
+pub mod piece;
+pub mod san;
+pub mod see;
+pub mod square;
+pub mod validate;
+pub mod zobrist;
@@ -0,0 +1,199 @@
+use std::sync::OnceLock;
+
+use bitboard::{self, Bitboard};
+use square::Square;
+
+/// Fancy magic bitboards for bishop and rook attacks.
+///
+/// For every square, the pieces that can block a slider are picked out of
+/// the occupancy with a mask, multiplied by a magic number and shifted down
+/// to an index into that square's slice of one shared attack table. The
+/// magic numbers are searched for with fixed seeds the first time the
+/// tables are used, which takes well under a tenth of a second.
+struct Magic {
+    /// the squares whose occupancy changes the attacks, edges excluded
+    mask: Bitboard,
+    magic: u64,
+    shift: u32,
+    /// where this square's attacks start in `Tables::attacks`
+    offset: usize
+}
+
+impl Magic {
+    fn index(&self, occupancy: Bitboard) -> usize {
+        self.offset + ((occupancy & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+struct Tables {
+    bishops: Vec<Magic>,
+    rooks: Vec<Magic>,
+    attacks: Vec<Bitboard>
+}
+
+static TABLES: OnceLock<Tables> = OnceLock::new();
+
+const SEEDS: [u64; 8] = [728, 10316, 55013, 32803, 12281, 15100, 16645, 255];
+
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+
+/// builds the attack tables now rather than on the first lookup
+pub fn init() {
+    tables();
+}
+
+fn tables() -> &'static Tables {
+    TABLES.get_or_init(Tables::new)
+}
+
+pub fn bishop_attacks(square: Square, occupancy: Bitboard) -> Bitboard {
+    let tables = tables();
+    tables.attacks[tables.bishops[square.index()].index(occupancy)]
+}
+
+pub fn rook_attacks(square: Square, occupancy: Bitboard) -> Bitboard {
+    let tables = tables();
+    tables.attacks[tables.rooks[square.index()].index(occupancy)]
+}
+
+impl Tables {
+    fn new() -> Tables {
+        let mut tables = Tables { bishops: Vec::with_capacity(64), rooks: Vec::with_capacity(64),
+                                  attacks: Vec::new() };
+        for index in 0..64 {
+            let square = Square::from_index(index);
+            let bishop = find_magic(square, &BISHOP_DIRECTIONS, &mut tables.attacks);
+            tables.bishops.push(bishop);
+            let rook = find_magic(square, &ROOK_DIRECTIONS, &mut tables.attacks);
+            tables.rooks.push(rook);
+        }
+        tables
+    }
+}
+
+/// walks each direction from the square until the edge of the board or the
+/// first occupied square, which is included
+fn ray_attacks(square: Square, occupancy: Bitboard, directions: &[(i8, i8)]) -> Bitboard {
+    let mut attacks = bitboard::EMPTY;
+    for &(file_step, rank_step) in directions {
+        let mut file = square.file() as i8 + file_step;
+        let mut rank = square.rank() as i8 + rank_step;
+        while (0..8).contains(&file) && (0..8).contains(&rank) {
+            let square = 1u64 << (rank * 8 + file);
+            attacks |= square;
+            if occupancy & square != 0 {
+                break;
+            }
+            file += file_step;
+            rank += rank_step;
+        }
+    }
+    attacks
+}
+
+/// the squares along each direction that could block the slider. The last
+/// square of a ray never matters since nothing lies behind it.
+fn blocker_mask(square: Square, directions: &[(i8, i8)]) -> Bitboard {
+    let mut mask = bitboard::EMPTY;
+    for &(file_step, rank_step) in directions {
+        let mut file = square.file() as i8 + file_step;
+        let mut rank = square.rank() as i8 + rank_step;
+        while (0..8).contains(&(file + file_step)) && (0..8).contains(&(rank + rank_step)) {
+            mask |= 1u64 << (rank * 8 + file);
+            file += file_step;
+            rank += rank_step;
+        }
+    }
+    mask
+}
+
+/// searches for a magic number that maps every blocker arrangement of the
+/// square to a slot holding its attacks, and appends those slots to `attacks`
+fn find_magic(square: Square, directions: &[(i8, i8)], attacks: &mut Vec<Bitboard>) -> Magic {
+    // seeds per rank that are known to find magics quickly
+    let mut random = Random(SEEDS[square.rank() as usize]);
+    let mask = blocker_mask(square, directions);
+    let bits = mask.count_ones();
+    let size = 1 << bits;
+
+    // enumerate every subset of the mask with the carry-rippler trick
+    let mut occupancies = Vec::with_capacity(size);
+    let mut reference = Vec::with_capacity(size);
+    let mut subset = bitboard::EMPTY;
+    loop {
+        occupancies.push(subset);
+        reference.push(ray_attacks(square, subset, directions));
+        subset = subset.wrapping_sub(mask) & mask;
+        if subset == bitboard::EMPTY {
+            break;
+        }
+    }
+
+    let offset = attacks.len();
+    attacks.resize(offset + size, bitboard::EMPTY);
+    // which attempt last wrote each slot, so the slots don't need clearing
+    let mut epoch = vec![0u32; size];
+    let mut attempt = 0;
+    loop {
+        let magic = random.sparse();
+        // a good magic moves the high bits of the mask to the top byte
+        if (mask.wrapping_mul(magic) >> 56).count_ones() < 6 {
+            continue;
+        }
+        attempt += 1;
+        let candidate = Magic { mask, magic, shift: 64 - bits, offset };
+        let works = occupancies.iter().zip(reference.iter()).all(|(&occupancy, &reference)| {
+            let slot = candidate.index(occupancy) - offset;
+            if epoch[slot] < attempt {
+                epoch[slot] = attempt;
+                attacks[offset + slot] = reference;
+                true
+            } else {
+                attacks[offset + slot] == reference
+            }
+        });
+        if works {
+            return candidate;
+        }
+    }
+}
+
+/// xorshift64 generator, seeded so the magics are the same every run
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(2_685_821_657_736_338_717)
+    }
+
+    /// a random number with few bits set, which makes a better magic
+    fn sparse(&mut self) -> u64 {
+        self.next() & self.next() & self.next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use magic;
+    use square::Square;
+
+    #[test]
+    fn test_magic_matches_rays() {
+        // a handful of scattered blockers on every square
+        let occupancies = [0u64, 0x0000_1008_0402_0100, 0x8142_2418_1824_4281, 0x00ff_0000_0000_ff00,
+                           0x1234_5678_9abc_def0];
+        for index in 0..64 {
+            let square = Square::from_index(index);
+            for &occupancy in occupancies.iter() {
+                assert_eq!(magic::bishop_attacks(square, occupancy),
+                           magic::ray_attacks(square, occupancy, &magic::BISHOP_DIRECTIONS));
+                assert_eq!(magic::rook_attacks(square, occupancy),
+                           magic::ray_attacks(square, occupancy, &magic::ROOK_DIRECTIONS));
+            }
+        }
+    }
+}
@@ -1,32 +1,30 @@
-extern crate rand;
-
-mod board;
-mod color;
-mod evaluation;
-mod logic;
-mod moves;
-mod piece;
-mod zobrist;
+extern crate ruci;
 
 use std::collections::HashMap;
-use std::io;
+use std::fs;
+use std::io::{self, Write};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
-use board::Board;
-use moves::State;
-use zobrist::Table;
+use ruci::board::Board;
+use ruci::epd::Epd;
+use ruci::evaluation::{self, Deadline, Limit};
+use ruci::magic;
+use ruci::moves::State;
+use ruci::perft;
+use ruci::san;
 
 fn readline() -> io::Result<String> {
     let mut buffer = String::new();
-    try!(io::stdin().read_line(&mut buffer));
+    io::stdin().read_line(&mut buffer)?;
     Ok(buffer)
 }
 
 fn uci_info() {
     println!("id name ruci");
     println!("id author J. Austin");
-    // TODO: add options?
+    println!("option name UCI_Chess960 type check default false");
     println!("uciok");
 }
 
@@ -39,29 +37,37 @@ fn uci_new_game() {
     // TODO
 }
 
-fn print_position(input: &Vec<&str>) {
-	if input.len() > 1 {
-		if input[1] == "fen" {
-			let mut s = input[2].to_owned();
-			s.push_str(" ");
-			s.push_str(input[3]);		
-			s.push_str(" ");
-			s.push_str(input[4]);		
-			s.push_str(" ");
-			s.push_str(input[5]);		
-			s.push_str(" ");
-			s.push_str(input[6]);		
-			s.push_str(" ");
-			s.push_str(input[7]);		
-			let board = Board::from_fen(&s);
-			board.print_board();
-		}
-	}
-}
-
-fn parse_go_command(game_state: Arc<Mutex<State>>) {
+fn print_position(input: &[&str]) {
+    if input.len() > 1 && input[1] == "fen" {
+        match Board::from_fen(&input[2..].join(" ")) {
+            Ok(board) => {
+                if let Err(e) = board.validate() {
+                    println!("illegal position: {}", e);
+                }
+                board.print_board()
+            },
+            Err(e) => println!("invalid fen: {}", e)
+        }
+    }
+}
+
+fn parse_go_command(game_state: Arc<Mutex<State>>, input: &[&str]) {
+    if input.get(1) == Some(&"perft") {
+        if let Some((depth, options)) = parse_perft(&input[1..]) {
+            // copied out so the state is not locked for the whole count
+            let (board, chess960) = {
+                let state = game_state.lock().unwrap();
+                (state.board, state.chess960)
+            };
+            thread::spawn(move || {
+                run_perft(&board, chess960, depth, options, true);
+            });
+        }
+        return;
+    }
+    let limit = Limit::from_go(input);
     thread::spawn(move || {
-        game_state.lock().unwrap().go();
+        game_state.lock().unwrap().go(limit);
     });
 }
 
@@ -73,71 +79,242 @@ fn ponder_hit() {
     // TODO
 }
 
-fn evaluate_position(input: &Vec<&str>) {
-    if input.len() > 1 {
-        let depth = input[1].parse::<u8>().unwrap();
-        let mut s = input[2].to_owned();
-        s.push_str(" ");
-        s.push_str(input[3]);		
-        s.push_str(" ");
-        s.push_str(input[4]);		
-        s.push_str(" ");
-        s.push_str(input[5]);		
-        s.push_str(" ");
-        s.push_str(input[6]);		
-        s.push_str(" ");
-        s.push_str(input[7]);		
-        let board = Board::from_fen(&s);
-        let mut line = Vec::new();
-        let mut table = HashMap::new();
-        let zobrist = Table::new();
-        println!("eval: {}", evaluation::pvs(&board, -5000.0, 5000.0, depth, 
-                                             &mut line, &mut table, &zobrist));
-        print!("bestmoves: ");
-        for m in line {
-            print!("{}", m);
+fn evaluate_position(input: &[&str]) {
+    let depth = match input.get(1).and_then(|depth| depth.parse::<u8>().ok()) {
+        Some(depth) => depth,
+        None => {
+            println!("usage: eval <depth> <fen>");
+            return;
+        }
+    };
+    let mut board = match Board::from_fen(&input[2..].join(" ")) {
+        Ok(board) => board,
+        Err(e) => {
+            println!("invalid fen: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = board.validate() {
+        println!("illegal position: {}", e);
+        return;
+    }
+    let mut line = Vec::new();
+    let mut table = HashMap::new();
+    println!("eval: {}", evaluation::pvs(&mut board, -5000.0, 5000.0, depth,
+                                         &mut line, &mut table, &mut Vec::new(), &mut Deadline::none()));
+    println!("bestmoves: {}", san::line_to_san(&board, &line).join(" "));
+}
+
+/// positions checked by `evalsym` when no file of FENs is given
+const SYMMETRY_FENS: [&str; 5] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2",
+    "r1bqk2r/pppp1pbp/2n2np1/1B2p3/4P3/2P2N2/PP1P1PPP/RNBQ1RK1 w kq - 1 6",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+];
+
+/// evaluates each position and its mirror image, which should score the
+/// same, reading one FEN per line from the given file if there is one
+fn eval_symmetry(input: &[&str]) {
+    let fens = match input.get(1) {
+        Some(path) => match fs::read_to_string(path) {
+            Ok(contents) => contents.lines().map(|line| line.trim().to_owned())
+                .filter(|line| !line.is_empty()).collect::<Vec<_>>(),
+            Err(e) => {
+                println!("could not read {}: {}", path, e);
+                return;
+            }
+        },
+        None => SYMMETRY_FENS.iter().map(|&fen| fen.to_owned()).collect()
+    };
+    let mut asymmetric = 0;
+    for fen in &fens {
+        let board = match Board::from_fen(fen) {
+            Ok(board) => board,
+            Err(e) => {
+                println!("invalid fen: {}: {}", e, fen);
+                continue;
+            }
+        };
+        if !evaluation::is_symmetric(&board) {
+            asymmetric += 1;
+            println!("asymmetric: {} evaluates to {} but its mirror {} to {}", fen,
+                     evaluation::evaluate_position(&board), board.mirror().to_fen(),
+                     evaluation::evaluate_position(&board.mirror()));
+        }
+    }
+    println!("{} of {} positions asymmetric", asymmetric, fens.len());
+}
+
+/// prints the current game as PGN, or appends it to the given file
+fn write_pgn(game_state: &State, input: &[&str]) {
+    let pgn = game_state.game.to_pgn();
+    match input.get(1) {
+        Some(path) => {
+            let written = fs::OpenOptions::new().create(true).append(true).open(path)
+                .and_then(|mut file| writeln!(file, "{}", pgn));
+            if let Err(e) = written {
+                println!("could not write {}: {}", path, e);
+            }
+        },
+        None => print!("{}", pgn)
+    }
+}
+
+/// runs the search on every position of an EPD test suite and counts the
+/// ones where it finds a best move, given "depth <plies>" or "movetime <ms>"
+fn run_suite(input: &[&str]) {
+    let limit = match (input.get(2), input.get(3).and_then(|n| n.parse::<u64>().ok())) {
+        (Some(&"depth"), Some(depth)) if depth > 0 && depth <= 255 => Limit::Depth(depth as u8),
+        (Some(&"movetime"), Some(ms)) => Limit::MoveTime(Duration::from_millis(ms)),
+        _ => {
+            println!("usage: suite <file> depth <plies> | suite <file> movetime <ms>");
+            return;
+        }
+    };
+    let contents = match fs::read_to_string(input[1]) {
+        Ok(contents) => contents,
+        Err(e) => {
+            println!("could not read {}: {}", input[1], e);
+            return;
+        }
+    };
+    let (mut solved, mut total) = (0, 0);
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let epd = match line.parse::<Epd>() {
+            Ok(epd) => epd,
+            Err(e) => {
+                println!("line {}: {}", i + 1, e);
+                continue;
+            }
+        };
+        if let Err(e) = epd.board.validate() {
+            println!("line {}: illegal position: {}", i + 1, e);
+            continue;
+        }
+        total += 1;
+        let id = epd.id.clone().unwrap_or_else(|| format!("line {}", i + 1));
+        let mut board = epd.board;
+        let (line, score) = evaluation::search(&mut board, limit, &mut HashMap::new(), &mut Vec::new(),
+                                               &mut |_, _, _| ());
+        let expected = san::line_to_san(&epd.board, &epd.best_moves).join(" ");
+        match line.first() {
+            Some(&m) if epd.is_solved_by(m, score) => {
+                solved += 1;
+                println!("{}: solved with {}", id, epd.board.to_san(m));
+            },
+            Some(&m) => println!("{}: failed, played {} instead of {}", id, epd.board.to_san(m), expected),
+            None => println!("{}: failed, no move found", id)
         }
-        println!("");
     }
+    println!("solved {} of {}", solved, total);
+}
+
+/// reads "perft <depth> [hash] [threads <n>]", printing the usage if it is
+/// malformed
+fn parse_perft(input: &[&str]) -> Option<(u8, perft::Options)> {
+    let depth = input.get(1).and_then(|depth| depth.parse::<u8>().ok());
+    let mut options = perft::Options::default();
+    let mut rest = input.iter().skip(2);
+    let mut valid = depth.is_some();
+    while let Some(&option) = rest.next() {
+        match option {
+            "hash" => options.hash = true,
+            "threads" => match rest.next().and_then(|n| n.parse::<usize>().ok()) {
+                Some(threads) if threads > 0 => options.threads = threads,
+                _ => valid = false
+            },
+            _ => valid = false
+        }
+    }
+    let depth = match depth {
+        Some(depth) if valid => depth,
+        _ => {
+            println!("usage: {} <depth> [hash] [threads <n>]", input[0]);
+            return None;
+        }
+    };
+    Some((depth, options))
+}
+
+/// counts the leaf nodes below the current position, and with divide also
+/// prints the count below each root move
+fn run_perft(board: &Board, chess960: bool, depth: u8, options: perft::Options, divide: bool) {
+    let start = Instant::now();
+    let nodes = if divide {
+        let counts = perft::divide(board, depth, options);
+        for &(m, nodes) in &counts {
+            println!("{}: {}", m.to_uci(chess960), nodes);
+        }
+        println!();
+        if depth == 0 { 1 } else { counts.iter().map(|&(_, nodes)| nodes).sum() }
+    } else {
+        perft::perft(board, depth, options)
+    };
+    let ms = start.elapsed().as_millis().max(1);
+    println!("nodes {} time {} nps {}", nodes, ms, nodes as u128 * 1000 / ms);
 }
 
 fn tests() {
-    let mut board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    let mut board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
     board.print_board();
-    board = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+    board = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
     board.print_board();
-    board = Board::from_fen("rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2");
+    board = Board::from_fen("rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2").unwrap();
     board.print_board();
-    board = Board::from_fen("rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+    board = Board::from_fen("rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2").unwrap();
     board.print_board();
-    board = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 0 3");
+    board = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 0 3").unwrap();
     board.print_board();
-    board = Board::from_fen("r1bqk2r/pppp1pbp/2n2np1/1B2p3/4P3/2P2N2/PP1P1PPP/RNBQ1RK1 w kq - 1 6");
+    board = Board::from_fen("r1bqk2r/pppp1pbp/2n2np1/1B2p3/4P3/2P2N2/PP1P1PPP/RNBQ1RK1 w kq - 1 6").unwrap();
     board.print_board();
-    board = Board::from_fen("2k4R/8/2K5/8/8/8/8/8 b - - 0 45");
+    board = Board::from_fen("2k4R/8/2K5/8/8/8/8/8 b - - 0 45").unwrap();
     board.print_board();
 }
 
 fn main() {
+    magic::init();
     let game_state = Arc::new(Mutex::new(State::new()));
     loop {
         let game_state = game_state.clone();
         let input = readline();
         match input {
             Err(_) => println!("error reading input"),
+            // end of input
+            Ok(ref string) if string.is_empty() => break,
             Ok(string) => {
                 let tokens = string.split_whitespace().collect::<Vec<_>>();
+                if tokens.is_empty() {
+                    continue;
+                }
                 match tokens[0] {
                     "uci" => uci_info(),
                     "isready" => is_ready(),
                     "ucinewgame" => uci_new_game(),
+                    "setoption" => game_state.lock().unwrap().set_option(&tokens),
                     "position" => game_state.lock().unwrap().update_position(&tokens),
-                    "go" => parse_go_command(game_state),
+                    "go" => parse_go_command(game_state, &tokens),
                     "stop" => stop(),
                     "ponderhit" => ponder_hit(),
                     "test" => tests(),
-                    "print" => game_state.lock().unwrap().print_board(),
+                    "print" => if tokens.len() > 1 {
+                        print_position(&tokens)
+                    } else {
+                        game_state.lock().unwrap().print_board()
+                    },
+                    "pgn" => write_pgn(&game_state.lock().unwrap(), &tokens),
+                    "fen" => println!("{}", game_state.lock().unwrap().board.to_fen()),
                     "eval" => evaluate_position(&tokens),
+                    "evalsym" => eval_symmetry(&tokens),
+                    "perft" | "divide" => if let Some((depth, options)) = parse_perft(&tokens) {
+                        let state = game_state.lock().unwrap();
+                        run_perft(&state.board, state.chess960, depth, options, tokens[0] == "divide")
+                    },
+                    "suite" => run_suite(&tokens),
                     "quit" => break,
                     _ => println!("Unknown command: {}", tokens[0])
                 }
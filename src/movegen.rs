@@ -0,0 +1,377 @@
+use std::ops::{Deref, DerefMut};
+
+use bitboard::{self, Bitboard};
+use board::Board;
+use color::Color;
+use moves::Move;
+use piece::{Piece, Type};
+use square::Square;
+
+/// no legal chess position has more moves than this
+pub const MAX_MOVES: usize = 256;
+
+/// the pieces a pawn can promote to, best first
+pub const PROMOTION_TYPES: [Type; 4] = [Type::Queen, Type::Knight, Type::Rook, Type::Bishop];
+
+/// A list of moves stored inline, so generating moves never allocates.
+/// Derefs to a slice of the moves added so far.
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    len: usize
+}
+
+impl MoveList {
+    pub fn new() -> MoveList {
+        let null = Move::new(Square::new(0, 0), Square::new(0, 0));
+        MoveList { moves: [null; MAX_MOVES], len: 0 }
+    }
+
+    pub fn push(&mut self, m: Move) {
+        self.moves[self.len] = m;
+        self.len += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> MoveList {
+        MoveList::new()
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [Move];
+
+    fn deref(&self) -> &[Move] {
+        &self.moves[..self.len]
+    }
+}
+
+impl DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut [Move] {
+        &mut self.moves[..self.len]
+    }
+}
+
+/// which moves the generator should emit
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum GenType {
+    All,
+    /// moves that take a piece, including en passant and capturing promotions
+    Captures,
+    /// every move that is not a capture
+    Quiets
+}
+
+/// the side to move's king and the pieces pinned to it
+struct Pins {
+    king: Option<Square>,
+    pinned: Bitboard
+}
+
+impl Pins {
+    /// the squares the piece may move to without exposing its king
+    fn allowed(&self, from: Square) -> Bitboard {
+        match self.king {
+            Some(king) if bitboard::contains(self.pinned, from) => bitboard::line(king, from),
+            _ => !bitboard::EMPTY
+        }
+    }
+}
+
+impl Board {
+    /// adds every legal move for the side to move to the list
+    pub fn generate_moves(&self, list: &mut MoveList) {
+        self.generate(GenType::All, list);
+    }
+
+    /// adds every legal capture for the side to move to the list
+    pub fn generate_captures(&self, list: &mut MoveList) {
+        self.generate(GenType::Captures, list);
+    }
+
+    /// adds every legal move that is not a capture to the list
+    pub fn generate_quiets(&self, list: &mut MoveList) {
+        self.generate(GenType::Quiets, list);
+    }
+
+    /// works out the checks and pins once, so that every move emitted is
+    /// legal without having to play it
+    fn generate(&self, gen_type: GenType, list: &mut MoveList) {
+        let color = self.active_color;
+        let targets = match gen_type {
+            GenType::All => !self.occupied[color as usize],
+            GenType::Captures => self.occupied[color.opposite() as usize],
+            GenType::Quiets => !self.occupancy()
+        };
+        let king = match self.king_square(color) {
+            Some(king) => king,
+            None => {
+                // only in a broken position, but the other pieces can still move
+                let pins = Pins { king: None, pinned: bitboard::EMPTY };
+                self.generate_piece_moves(targets, &pins, list);
+                self.generate_pawn_moves(gen_type, targets, &pins, list);
+                return
+            }
+        };
+        let checkers = self.attackers_to(king, color.opposite());
+        let pins = Pins { king: Some(king), pinned: self.pinned(color) };
+        if checkers == bitboard::EMPTY {
+            self.generate_non_evasions(gen_type, targets, &pins, list);
+        } else {
+            self.generate_evasions(gen_type, targets, checkers, &pins, list);
+        }
+    }
+
+    fn generate_non_evasions(&self, gen_type: GenType, targets: Bitboard, pins: &Pins, list: &mut MoveList) {
+        self.generate_pawn_moves(gen_type, targets, pins, list);
+        self.generate_piece_moves(targets, pins, list);
+        if let Some(king) = pins.king {
+            self.generate_king_moves(king, targets, list);
+            if gen_type != GenType::Captures {
+                self.generate_castles(king, list);
+            }
+        }
+    }
+
+    /// the king steps out of check, or with a single checker another piece
+    /// takes it or moves in between
+    fn generate_evasions(&self, gen_type: GenType, targets: Bitboard, checkers: Bitboard, pins: &Pins,
+                         list: &mut MoveList) {
+        let king = match pins.king {
+            Some(king) => king,
+            None => return
+        };
+        self.generate_king_moves(king, targets, list);
+        // nothing else can stop two checks at once
+        if checkers.count_ones() > 1 {
+            return
+        }
+        let checker = Square::from_index(checkers.trailing_zeros() as u8);
+        let blocks = targets & (checkers | bitboard::between(king, checker));
+        self.generate_pawn_moves(gen_type, blocks, pins, list);
+        self.generate_piece_moves(blocks, pins, list);
+    }
+
+    /// knight, bishop, rook and queen moves onto the target squares
+    fn generate_piece_moves(&self, targets: Bitboard, pins: &Pins, list: &mut MoveList) {
+        let color = self.active_color;
+        let occupancy = self.occupancy();
+        let enemy = self.occupied[color.opposite() as usize];
+        for &piece_type in &[Type::Knight, Type::Bishop, Type::Rook, Type::Queen] {
+            for from in bitboard::squares(self.pieces_of(color, piece_type)) {
+                let attacks = match piece_type {
+                    Type::Knight => bitboard::knight_attacks(from),
+                    Type::Bishop => bitboard::bishop_attacks(from, occupancy),
+                    Type::Rook => bitboard::rook_attacks(from, occupancy),
+                    _ => bitboard::queen_attacks(from, occupancy)
+                };
+                for to in bitboard::squares(attacks & targets & pins.allowed(from)) {
+                    let mut m = Move::new(from, to);
+                    if bitboard::contains(enemy, to) {
+                        m.flags |= Move::CAPTURE;
+                    }
+                    list.push(m);
+                }
+            }
+        }
+    }
+
+    fn generate_king_moves(&self, king: Square, targets: Bitboard, list: &mut MoveList) {
+        let color = self.active_color;
+        let enemy = self.occupied[color.opposite() as usize];
+        // sliders see through the king, so it can't step back along their line
+        let occupancy = self.occupancy() & !bitboard::from_square(king);
+        for to in bitboard::squares(bitboard::king_attacks(king) & targets) {
+            if self.attackers_to_occupied(to, color.opposite(), occupancy) != bitboard::EMPTY {
+                continue;
+            }
+            let mut m = Move::new(king, to);
+            if bitboard::contains(enemy, to) {
+                m.flags |= Move::CAPTURE;
+            }
+            list.push(m);
+        }
+    }
+
+    /// pawn moves onto the target squares. En passant is checked on its
+    /// own since it takes a pawn that is not on the target square.
+    fn generate_pawn_moves(&self, gen_type: GenType, targets: Bitboard, pins: &Pins, list: &mut MoveList) {
+        let color = self.active_color;
+        let occupancy = self.occupancy();
+        let enemy = self.occupied[color.opposite() as usize];
+        let (forward, start_rank, last_rank): (i8, u8, u8) = match color {
+            Color::White => (1, 1, 7),
+            Color::Black => (-1, 6, 0)
+        };
+
+        for from in bitboard::squares(self.pieces_of(color, Type::Pawn)) {
+            // only possible in a broken position, but there is nowhere to go
+            if from.rank() == last_rank {
+                continue;
+            }
+            let allowed = targets & pins.allowed(from);
+            if gen_type != GenType::Quiets {
+                for to in bitboard::squares(bitboard::pawn_attacks(color, from) & enemy & allowed) {
+                    let mut m = Move::new(from, to);
+                    m.flags |= Move::CAPTURE;
+                    push_pawn_move(list, m, last_rank);
+                }
+                if let Some(to) = self.en_passant_square {
+                    if bitboard::contains(bitboard::pawn_attacks(color, from), to) &&
+                        self.is_en_passant_legal(from, to) {
+                        let mut m = Move::new(from, to);
+                        m.flags |= Move::CAPTURE | Move::EN_PASSANT;
+                        list.push(m);
+                    }
+                }
+            }
+            if gen_type != GenType::Captures {
+                let one = Square::new(from.file(), (from.rank() as i8 + forward) as u8);
+                if !bitboard::contains(occupancy, one) {
+                    if bitboard::contains(allowed, one) {
+                        push_pawn_move(list, Move::new(from, one), last_rank);
+                    }
+                    if from.rank() == start_rank {
+                        let two = Square::new(from.file(), (one.rank() as i8 + forward) as u8);
+                        if !bitboard::contains(occupancy, two) && bitboard::contains(allowed, two) {
+                            let mut m = Move::new(from, two);
+                            m.flags |= Move::DOUBLE_PUSH;
+                            list.push(m);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// en passant empties two squares on one rank at once, which can uncover
+    /// the king in ways a pin doesn't describe, so the capture is tested
+    /// against the occupancy it leaves behind
+    fn is_en_passant_legal(&self, from: Square, to: Square) -> bool {
+        let color = self.active_color;
+        let king = match self.king_square(color) {
+            Some(king) => king,
+            None => return true
+        };
+        let captured = bitboard::from_square(Square::new(to.file(), from.rank()));
+        let occupancy = (self.occupancy() & !bitboard::from_square(from) & !captured) |
+            bitboard::from_square(to);
+        self.attackers_to_occupied(king, color.opposite(), occupancy) & !captured == bitboard::EMPTY
+    }
+
+    /// castles are generated as the king taking its own rook, which also
+    /// covers Chess960 positions where the king or rook barely move
+    fn generate_castles(&self, king: Square, list: &mut MoveList) {
+        let color = self.active_color;
+        for &kingside in &[true, false] {
+            if !self.castling_availability.get(color, kingside) {
+                continue;
+            }
+            let rook = self.castling_rook(color, kingside);
+            if self.piece_at(rook) != Some(Piece { piece_type: Type::Rook, color }) ||
+                rook.rank() != king.rank() || (rook.file() > king.file()) != kingside {
+                continue;
+            }
+            let mut m = Move::new(king, rook);
+            m.flags |= Move::CASTLE;
+            let (king_to, rook_to) = m.castling_squares();
+            let king_path = bitboard::between(king, king_to) | bitboard::from_square(king_to);
+            let rook_path = bitboard::between(rook, rook_to) | bitboard::from_square(rook_to);
+            let castlers = bitboard::from_square(king) | bitboard::from_square(rook);
+            let occupancy = self.occupancy() & !castlers;
+            if (king_path | rook_path) & occupancy != bitboard::EMPTY {
+                continue;
+            }
+            // the king may not pass through or land on an attacked square
+            if bitboard::squares(king_path).all(|square| {
+                self.attackers_to_occupied(square, color.opposite(), occupancy) == bitboard::EMPTY
+            }) {
+                list.push(m);
+            }
+        }
+    }
+}
+
+/// adds the pawn move, or one move per promotion piece if it reaches the last rank
+fn push_pawn_move(list: &mut MoveList, m: Move, last_rank: u8) {
+    if m.to.rank() == last_rank {
+        for &piece_type in &PROMOTION_TYPES {
+            list.push(Move { promotion: Some(piece_type), ..m });
+        }
+    } else {
+        list.push(m);
+    }
+}
+
+/// the number of legal moves each side would have if it were their turn
+pub fn mobility(board: &Board) -> [usize; 2] {
+    let mut list = MoveList::new();
+    let mut output = [0; 2];
+    let mut board = *board;
+    for _ in 0..2 {
+        list.clear();
+        board.generate_moves(&mut list);
+        output[board.active_color as usize] = list.len();
+        board.active_color = board.active_color.opposite();
+        board.en_passant_square = None;
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use board::Board;
+    use movegen::MoveList;
+
+    fn count(fen: &str) -> (usize, usize, usize) {
+        let board = Board::from_fen(fen).unwrap();
+        let (mut all, mut captures, mut quiets) = (MoveList::new(), MoveList::new(), MoveList::new());
+        board.generate_moves(&mut all);
+        board.generate_captures(&mut captures);
+        board.generate_quiets(&mut quiets);
+        (all.len(), captures.len(), quiets.len())
+    }
+
+    #[test]
+    fn test_generate_moves_start() {
+        assert_eq!(count("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), (20, 0, 20));
+    }
+
+    #[test]
+    fn test_generate_moves_kiwipete() {
+        assert_eq!(count("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"),
+                   (48, 8, 40));
+    }
+
+    #[test]
+    fn test_generate_moves_promotions() {
+        // four promotions straight ahead and four capturing a knight
+        assert_eq!(count("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1"), (13, 4, 9));
+    }
+
+    #[test]
+    fn test_generate_moves_pinned() {
+        // the rook can only move along the file it is pinned on
+        assert_eq!(count("4k3/4r3/8/8/8/8/4R3/4K3 w - - 0 1"), (9, 1, 8));
+    }
+
+    #[test]
+    fn test_generate_evasions() {
+        // take the queen or step aside
+        assert_eq!(count("4k3/8/8/8/8/8/3q4/4K3 w - - 0 1"), (2, 1, 1));
+        // in double check only the king may move, even with a rook to take
+        assert_eq!(count("4k3/8/8/8/8/3n4/8/R3K2r w - - 0 1"), (2, 0, 2));
+    }
+
+    #[test]
+    fn test_generate_castles_chess960() {
+        // the king stays put while the rook jumps over to d1
+        assert_eq!(count("4k3/8/8/8/8/8/8/1RK5 w B - 0 1"), (13, 0, 13));
+        // unless the rook was all that shielded the king from the queen
+        assert_eq!(count("4k3/8/8/8/8/8/8/qRK5 w B - 0 1"), (4, 1, 3));
+    }
+}
@@ -1,22 +1,156 @@
 use std::collections::HashMap;
-use std::f64;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Instant;
 
-use board::{Board, Location};
-use evaluation;
-use zobrist::{Entry, Table};
+use board::{self, Board};
+use evaluation::{self, Limit};
+use game::Game;
+use logic;
+use outcome::Outcome;
+use piece::Type;
+use square::Square;
+use zobrist::Entry;
+
+/// a single chess move, along with what kind of move it is
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Move {
+    pub from: Square,
+    pub to: Square,
+    /// the piece a pawn turns into on the last rank
+    pub promotion: Option<Type>,
+    /// a combination of `CAPTURE`, `EN_PASSANT`, `CASTLE` and `DOUBLE_PUSH`
+    pub flags: u8
+}
+
+impl Move {
+    pub const CAPTURE: u8 = 1;
+    pub const EN_PASSANT: u8 = 1 << 1;
+    pub const CASTLE: u8 = 1 << 2;
+    pub const DOUBLE_PUSH: u8 = 1 << 3;
+
+    /// a move with no promotion and no flags set
+    pub fn new(from: Square, to: Square) -> Move {
+        Move { from, to, promotion: None, flags: 0 }
+    }
+
+    pub fn is_capture(self) -> bool {
+        self.flags & Move::CAPTURE != 0
+    }
+
+    pub fn is_en_passant(self) -> bool {
+        self.flags & Move::EN_PASSANT != 0
+    }
+
+    pub fn is_castle(self) -> bool {
+        self.flags & Move::CASTLE != 0
+    }
+
+    pub fn is_double_push(self) -> bool {
+        self.flags & Move::DOUBLE_PUSH != 0
+    }
+
+    /// where the king and rook end up after a castle, which is stored as the
+    /// king taking its own rook so that Chess960 castles are unambiguous
+    pub fn castling_squares(self) -> (Square, Square) {
+        let rank = self.from.rank();
+        if self.to.file() > self.from.file() {
+            (Square::new(6, rank), Square::new(5, rank))
+        } else {
+            (Square::new(2, rank), Square::new(3, rank))
+        }
+    }
+
+    /// the move in UCI notation. In Chess960 mode castles are written as the
+    /// king taking its rook, otherwise as the king's two square step.
+    pub fn to_uci(self, chess960: bool) -> String {
+        if chess960 && self.is_castle() {
+            format!("{}{}", self.from, self.to)
+        } else {
+            self.to_string()
+        }
+    }
+}
+
+/// writes the move in UCI long algebraic notation, such as "e2e4" or "e7e8q"
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let to = if self.is_castle() { self.castling_squares().0 } else { self.to };
+        write!(f, "{}{}", self.from, to)?;
+        match self.promotion {
+            Some(Type::Queen) => write!(f, "q"),
+            Some(Type::Rook) => write!(f, "r"),
+            Some(Type::Bishop) => write!(f, "b"),
+            Some(Type::Knight) => write!(f, "n"),
+            _ => Ok(())
+        }
+    }
+}
+
+/// returned when a string is not a move in UCI notation
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseMoveError(pub String);
+
+impl fmt::Display for ParseMoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid move '{}'", self.0)
+    }
+}
+
+impl Error for ParseMoveError {}
+
+/// parses a move in UCI notation. Only the squares and promotion piece are
+/// known from the text, so the flags are left empty; use
+/// `logic::parse_move` to get the move with its flags for a given board.
+impl FromStr for Move {
+    type Err = ParseMoveError;
+
+    fn from_str(s: &str) -> Result<Move, ParseMoveError> {
+        let error = || ParseMoveError(s.to_owned());
+        if !s.is_ascii() || (s.len() != 4 && s.len() != 5) {
+            return Err(error());
+        }
+        let from = s[..2].parse::<Square>().map_err(|_| error())?;
+        let to = s[2..4].parse::<Square>().map_err(|_| error())?;
+        let promotion = match s[4..].chars().next() {
+            None => None,
+            Some('q') => Some(Type::Queen),
+            Some('r') => Some(Type::Rook),
+            Some('b') => Some(Type::Bishop),
+            Some('n') => Some(Type::Knight),
+            Some(_) => return Err(error())
+        };
+        Ok(Move { from, to, promotion, flags: 0 })
+    }
+}
 
 pub struct State {
     pub board: Board,
     pub hashmap: HashMap<u64, Entry>,
-    pub zobrist: Table,
+    /// the hashes of the positions before the current one, back to the
+    /// last capture or pawn move
+    pub history: Vec<u64>,
+    /// set by the UCI_Chess960 option, which writes castles as king takes rook
+    pub chess960: bool,
+    /// the game so far, from the last position set with a FEN or startpos
+    pub game: Game,
+}
+
+impl Default for State {
+    fn default() -> State {
+        State::new()
+    }
 }
 
 impl State {
     pub fn new() -> State {
         State {
-            board: Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            board: Board::from_fen(board::START_FEN).unwrap(),
             hashmap: HashMap::new(),
-            zobrist: Table::new(),
+            history: Vec::new(),
+            chess960: false,
+            game: Game::new(),
         }
     }
 
@@ -24,66 +158,173 @@ impl State {
         self.board.print_board();
     }
 
-    pub fn update_position(&mut self, input: &Vec<&str>) {
-        if input[1] == "fen" {
-            let mut s = input[2].to_owned();
-            s.push_str(" ");
-            s.push_str(input[3]);
-            s.push_str(" ");
-            s.push_str(input[4]);
-            s.push_str(" ");
-            s.push_str(input[5]);
-            s.push_str(" ");
-            s.push_str(input[6]);
-            s.push_str(" ");
-            s.push_str(input[7]);
-            self.board = Board::from_fen(&s);
-        } else if input[1] == "startpos" {
-            self.board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
-        }
+    pub fn update_position(&mut self, input: &[&str]) {
         // skip in input until just after the word 'moves'
         let index = input.iter().position(|&r| r == "moves").unwrap_or(input.len());
-        for i in (index + 1)..input.len() {
-            let m = input[i];
-            let mut start = "".to_owned();
-            let mut end = "".to_owned();
-            // i feel like substring shouldn't be this hard...
-            let mut j = 0;
-            for ch in m.chars() {
-                if j < 2 {
-                    start.push(ch);
-                } else {
-                    end.push(ch);
+        if input.len() > 1 && input[1] == "fen" {
+            let board = match Board::from_fen(&input[2..index].join(" ")) {
+                Ok(board) => board,
+                Err(e) => {
+                    println!("info string invalid fen: {}", e);
+                    return;
+                }
+            };
+            if let Err(e) = board.validate() {
+                println!("info string illegal position: {}", e);
+                return;
+            }
+            self.board = board;
+            self.history.clear();
+            self.game = Game::from_board(board);
+        } else if input.len() > 1 && input[1] == "startpos" {
+            self.board = Board::from_fen(board::START_FEN).unwrap();
+            self.history.clear();
+            self.game = Game::new();
+        }
+        for m in input.iter().skip(index + 1) {
+            let m = match logic::parse_move(&self.board, m) {
+                Some(m) => m,
+                None => {
+                    println!("info string illegal move: {}", m);
+                    return;
                 }
-                j += 1;
+            };
+            self.history.push(self.board.hash);
+            self.board.make_move(m);
+            self.game.push(m);
+            // no earlier position can come back after a capture or pawn move
+            if self.board.halfmove_clock == 0 {
+                self.history.clear();
             }
-            let start_loc = Location::parse_notation(&start);
-            let end_loc = Location::parse_notation(&end);
-            self.board = self.board.after_move(start_loc, end_loc);
         }
     }
 
-    pub fn go(&mut self) {
-        let mut depth = 1;
-        let mut best_move = "".to_owned();
-        while depth < 5 {
-            let mut line = Vec::new();
-            let score = evaluation::pvs(&self.board, f64::NEG_INFINITY, f64::INFINITY, depth, 
-                                        &mut line, &mut self.hashmap, &self.zobrist) * 100.0;
-            print!("info depth {} score cp {:.0} nodes {} time {} pv ", 
-                     depth, score, "1", "1");
-            for m in &line {
-                print!("{}", m);
-            }
-            println!("");
-            best_move = line[0].clone();
-            best_move.push_str(&line[1].clone());
+    /// handles "setoption name <name> value <value>"
+    pub fn set_option(&mut self, input: &[&str]) {
+        let value = input.iter().position(|&r| r == "value").unwrap_or(input.len());
+        let name = input[2.min(value)..value].join(" ");
+        let value = input[(value + 1).min(input.len())..].join(" ");
+        match name.as_str() {
+            "UCI_Chess960" => self.chess960 = value == "true",
+            _ => println!("info string unknown option: {}", name)
+        }
+    }
+
+    /// how the game has ended, counting repetitions of earlier positions
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.board.outcome_with_history(&self.history)
+    }
+
+    /// searches the current position to the limit and prints an info line
+    /// for each finished depth, then the best move
+    pub fn go(&mut self, limit: Limit) {
+        let start = Instant::now();
+        let chess960 = self.chess960;
+        let (line, _) = evaluation::search(&mut self.board, limit, &mut self.hashmap, &mut self.history,
+                                           &mut |depth, line, score| {
+            print!("info depth {} ", depth);
             if score.is_infinite() {
-                break;
+                // the line ends with the mating move
+                let moves = (line.len() as i32 + 1) / 2;
+                print!("score mate {} ", if score > 0.0 { moves } else { -moves });
+            } else {
+                print!("score cp {:.0} ", score * 100.0);
+            }
+            print!("time {} pv", start.elapsed().as_millis());
+            for m in line {
+                print!(" {}", m.to_uci(chess960));
             }
-            depth += 1;
+            println!();
+        });
+        match line.first() {
+            Some(m) => println!("bestmove {}", m.to_uci(self.chess960)),
+            // the null move, sent when there is no legal move
+            None => println!("bestmove 0000")
         }
-        println!("bestmove {}", best_move);
     }
 
 }
+
+#[cfg(test)]
+mod test {
+    use board::{self, Board};
+    use logic;
+    use moves::{Move, ParseMoveError, State};
+    use outcome::Outcome;
+    use piece::Type;
+    use square::Square;
+
+    #[test]
+    fn test_move_round_trip() {
+        for text in &["e2e4", "g1f3", "e7e8q", "a2a1n", "h7g8r", "b2c1b"] {
+            assert_eq!(text.parse::<Move>().unwrap().to_string(), *text);
+        }
+        let promotion = "e7e8n".parse::<Move>().unwrap();
+        assert_eq!(promotion.from, Square::new(4, 6));
+        assert_eq!(promotion.to, Square::new(4, 7));
+        assert_eq!(promotion.promotion, Some(Type::Knight));
+        assert_eq!(promotion.flags, 0);
+    }
+
+    #[test]
+    fn test_parse_move_errors() {
+        for text in &["", "e2e", "e2e4qq", "i2e4", "e2e9", "e7e8x", "e7e8Q", "e2é4"] {
+            assert_eq!(text.parse::<Move>(), Err(ParseMoveError(text.to_string())));
+        }
+        let board = Board::from_fen(board::START_FEN).unwrap();
+        assert_eq!(logic::parse_move(&board, "e2e"), None);
+        assert_eq!(logic::parse_move(&board, "e2e5"), None);
+        assert!(logic::parse_move(&board, "e2e4").unwrap().is_double_push());
+    }
+
+    #[test]
+    fn test_move_to_uci() {
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let castle = logic::parse_move(&board, "e1g1").unwrap();
+        assert_eq!(logic::parse_move(&board, "e1h1"), Some(castle));
+        assert_eq!(castle.to_string(), "e1g1");
+        assert_eq!(castle.to_uci(false), "e1g1");
+        assert_eq!(castle.to_uci(true), "e1h1");
+        // a king one step from where it castles to could mean a plain king move
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/5K1R w H - 0 1").unwrap();
+        assert!(!logic::parse_move(&board, "f1g1").unwrap().is_castle());
+        assert!(logic::parse_move(&board, "f1h1").unwrap().is_castle());
+    }
+
+    #[test]
+    fn test_state_chess960_option() {
+        let mut state = State::new();
+        state.set_option(&["setoption", "name", "UCI_Chess960", "value", "true"]);
+        assert!(state.chess960);
+        state.set_option(&["setoption", "name", "UCI_Chess960", "value", "false"]);
+        assert!(!state.chess960);
+    }
+
+    #[test]
+    fn test_state_threefold_repetition() {
+        let mut state = State::new();
+        let knights = "g1f3 g8f6 f3g1 f6g8";
+        state.update_position(&format!("position startpos moves {}", knights).split(' ').collect::<Vec<_>>());
+        assert_eq!(state.history.len(), 4);
+        assert_eq!(state.outcome(), None);
+        let moves = format!("position startpos moves {} {}", knights, knights);
+        state.update_position(&moves.split(' ').collect::<Vec<_>>());
+        assert_eq!(state.outcome(), Some(Outcome::ThreefoldRepetition));
+        // a pawn move means nothing before it can repeat
+        state.update_position(&format!("{} e2e4", moves).split(' ').collect::<Vec<_>>());
+        assert!(state.history.is_empty());
+        assert_eq!(state.game.main_line().len(), 9);
+    }
+
+    #[test]
+    fn test_state_repetition_after_double_push() {
+        // nothing can take on e3, so the position after 1. e4 comes back
+        // when the knights return
+        let mut state = State::new();
+        let moves = "position startpos moves e2e4 g8f6 g1f3 f6g8 f3g1 g8f6 g1f3 f6g8";
+        state.update_position(&moves.split(' ').collect::<Vec<_>>());
+        assert_eq!(state.outcome(), None);
+        state.update_position(&format!("{} f3g1", moves).split(' ').collect::<Vec<_>>());
+        assert_eq!(state.outcome(), Some(Outcome::ThreefoldRepetition));
+    }
+}
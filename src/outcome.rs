@@ -0,0 +1,127 @@
+use bitboard;
+use board::Board;
+use color::Color;
+use movegen::MoveList;
+use piece::Type;
+
+/// How a game has ended.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    /// the side to move is mated, so the given color has won
+    Checkmate(Color),
+    Stalemate,
+    /// a hundred half moves without a capture or pawn move
+    FiftyMoveRule,
+    /// neither side has the material left to mate
+    InsufficientMaterial,
+    /// the position has come up three times
+    ThreefoldRepetition
+}
+
+impl Outcome {
+    /// the color that won, or None for a draw
+    pub fn winner(self) -> Option<Color> {
+        match self {
+            Outcome::Checkmate(color) => Some(color),
+            _ => None
+        }
+    }
+}
+
+impl Board {
+    /// returns how the game has ended in this position, or None if it goes on.
+    /// Repetition can't be seen from the board alone, see `outcome_with_history`.
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome_with_history(&[])
+    }
+
+    /// like `outcome`, also checking for threefold repetition against the
+    /// hashes of the earlier positions of the game
+    pub fn outcome_with_history(&self, history: &[u64]) -> Option<Outcome> {
+        // mate on the hundredth half move still counts as mate
+        let mut moves = MoveList::new();
+        self.generate_moves(&mut moves);
+        if moves.is_empty() {
+            return Some(if self.checkers() != bitboard::EMPTY {
+                Outcome::Checkmate(self.active_color.opposite())
+            } else {
+                Outcome::Stalemate
+            })
+        }
+        if self.is_insufficient_material() {
+            return Some(Outcome::InsufficientMaterial)
+        }
+        if self.halfmove_clock >= 100 {
+            return Some(Outcome::FiftyMoveRule)
+        }
+        if history.iter().filter(|&&hash| hash == self.hash).count() >= 2 {
+            return Some(Outcome::ThreefoldRepetition)
+        }
+        None
+    }
+
+    /// returns true if no sequence of moves could mate either king: bare
+    /// kings, a single minor piece, or bishops that all share a square color
+    pub fn is_insufficient_material(&self) -> bool {
+        let both = |piece_type| self.pieces_of(Color::White, piece_type) | self.pieces_of(Color::Black, piece_type);
+        if both(Type::Pawn) | both(Type::Rook) | both(Type::Queen) != bitboard::EMPTY {
+            return false
+        }
+        let bishops = both(Type::Bishop);
+        let minors = bishops | both(Type::Knight);
+        minors.count_ones() <= 1 ||
+            minors == bishops && (bishops & bitboard::DARK_SQUARES == bitboard::EMPTY ||
+                                  bishops & !bitboard::DARK_SQUARES == bitboard::EMPTY)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use board::Board;
+    use color::Color;
+    use outcome::Outcome;
+
+    fn outcome(fen: &str) -> Option<Outcome> {
+        Board::from_fen(fen).unwrap().outcome()
+    }
+
+    #[test]
+    fn test_outcome_mate_and_stalemate() {
+        assert_eq!(outcome("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 0 3"),
+                   Some(Outcome::Checkmate(Color::Black)));
+        assert_eq!(outcome("kr6/p7/K7/8/2n5/8/8/8 w - - 22 22"), Some(Outcome::Stalemate));
+        assert_eq!(outcome("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), None);
+    }
+
+    #[test]
+    fn test_outcome_fifty_moves() {
+        assert_eq!(outcome("4k3/8/8/8/8/8/8/R3K3 b - - 99 80"), None);
+        assert_eq!(outcome("4k3/8/8/8/8/8/8/R3K3 b - - 100 80"), Some(Outcome::FiftyMoveRule));
+        // mate takes precedence
+        assert_eq!(outcome("R3k3/8/4K3/8/8/8/8/8 b - - 100 80"), Some(Outcome::Checkmate(Color::White)));
+    }
+
+    #[test]
+    fn test_outcome_insufficient_material() {
+        for fen in &["4k3/8/8/8/8/8/8/4K3 w - - 0 1", "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+                     "4k3/8/8/8/8/8/8/1N2K3 b - - 0 1", "2b1k3/8/8/8/8/8/8/3BK3 w - - 0 1",
+                     "4k3/8/8/8/8/8/1B6/B3K3 w - - 0 1"] {
+            assert_eq!(outcome(fen), Some(Outcome::InsufficientMaterial));
+        }
+        // opposite colored bishops, two knights and a pawn can all still mate
+        for fen in &["2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1", "4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1",
+                     "4k3/8/8/8/8/8/P7/4K3 w - - 0 1"] {
+            assert_eq!(outcome(fen), None);
+        }
+    }
+
+    #[test]
+    fn test_outcome_repetition() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(board.outcome_with_history(&[board.hash, 1, 2]), None);
+        assert_eq!(board.outcome_with_history(&[board.hash, 1, board.hash, 2]),
+                   Some(Outcome::ThreefoldRepetition));
+        assert_eq!(Outcome::ThreefoldRepetition.winner(), None);
+        assert_eq!(Outcome::Checkmate(Color::White).winner(), Some(Color::White));
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::thread;
+
+use board::Board;
+use movegen::MoveList;
+use moves::Move;
+
+/// how perft counts its nodes
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Options {
+    /// remember the count below each position and depth already seen
+    pub hash: bool,
+    /// split the root moves between this many threads
+    pub threads: usize
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options { hash: false, threads: 1 }
+    }
+}
+
+/// counts the leaf nodes of the move tree below the position to the given
+/// depth, the standard way to check move generation against known numbers
+pub fn perft(board: &Board, depth: u8, options: Options) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    divide(board, depth, options).iter().map(|&(_, nodes)| nodes).sum()
+}
+
+/// the leaf nodes below each root move, in the order the moves were generated
+pub fn divide(board: &Board, depth: u8, options: Options) -> Vec<(Move, u64)> {
+    let mut list = MoveList::new();
+    board.generate_moves(&mut list);
+    if depth == 0 || list.is_empty() {
+        return Vec::new();
+    }
+    let chunk_size = list.len().div_ceil(options.threads.max(1));
+    thread::scope(|scope| {
+        let handles = list.chunks(chunk_size).map(|chunk| scope.spawn(move || {
+            let mut board = *board;
+            // each thread keeps a table of its own, so none of them wait on a lock
+            let mut table = if options.hash { Some(HashMap::new()) } else { None };
+            chunk.iter().map(|&m| {
+                let undo = board.make_move(m);
+                let nodes = count(&mut board, depth - 1, table.as_mut());
+                board.unmake_move(m, undo);
+                (m, nodes)
+            }).collect::<Vec<_>>()
+        })).collect::<Vec<_>>();
+        handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+    })
+}
+
+fn count(board: &mut Board, depth: u8, mut table: Option<&mut HashMap<(u64, u8), u64>>) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    if let Some(&nodes) = table.as_ref().and_then(|table| table.get(&(board.hash, depth))) {
+        return nodes;
+    }
+    let mut list = MoveList::new();
+    board.generate_moves(&mut list);
+    // the moves are legal, so the leaves needn't be played
+    if depth == 1 {
+        return list.len() as u64;
+    }
+    let mut nodes = 0;
+    for &m in list.iter() {
+        let undo = board.make_move(m);
+        nodes += count(board, depth - 1, table.as_deref_mut());
+        board.unmake_move(m, undo);
+    }
+    if let Some(table) = table {
+        table.insert((board.hash, depth), nodes);
+    }
+    nodes
+}
+
+#[cfg(test)]
+mod test {
+    use board::Board;
+    use perft::{self, Options};
+
+    #[test]
+    fn test_divide() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        let counts = perft::divide(&board, 2, Options::default());
+        assert_eq!(counts.len(), 6);
+        let e4 = counts.iter().find(|&&(m, _)| m.to_string() == "e2e4").unwrap();
+        assert_eq!(e4.1, 5);
+        assert_eq!(perft::perft(&board, 2, Options::default()), counts.iter().map(|c| c.1).sum());
+        assert_eq!(perft::perft(&board, 0, Options::default()), 1);
+        let mate = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        assert_eq!(perft::divide(&mate, 3, Options::default()), vec![]);
+    }
+
+    #[test]
+    fn test_perft_options_agree() {
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let plain = perft::perft(&board, 2, Options::default());
+        assert_eq!(plain, 2039);
+        assert_eq!(perft::perft(&board, 2, Options { hash: true, threads: 4 }), plain);
+        assert_eq!(perft::perft(&board, 2, Options { hash: false, threads: 100 }), plain);
+    }
+}
@@ -0,0 +1,616 @@
+use std::error::Error;
+use std::fmt;
+use std::iter;
+use std::slice;
+use std::str::Chars;
+
+use board::{Board, FenError};
+use color::Color;
+use game::{Game, Node};
+use san::SanError;
+
+/// What went wrong while reading PGN.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PgnErrorKind {
+    /// a tag pair was not `[Name "value"]`
+    Tag,
+    /// a comment or tag value was still open at the end of the text
+    Unterminated,
+    /// a character that can't start any PGN token
+    UnexpectedChar(char),
+    /// a ')' with no variation to close
+    UnmatchedParen,
+    /// a '(' before any move it could be an alternative to
+    MisplacedVariation,
+    /// a variation was still open at the end of the game
+    UnclosedVariation,
+    /// the FEN tag could not be parsed
+    Fen(FenError),
+    /// a move could not be played from the position it was reached in
+    Move(SanError)
+}
+
+/// an error in PGN text, at the given line and column, both from 1
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PgnError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: PgnErrorKind
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match self.kind {
+            PgnErrorKind::Tag => write!(f, "invalid tag pair"),
+            PgnErrorKind::Unterminated => write!(f, "unterminated comment or string"),
+            PgnErrorKind::UnexpectedChar(ch) => write!(f, "unexpected character '{}'", ch),
+            PgnErrorKind::UnmatchedParen => write!(f, "')' without a variation to close"),
+            PgnErrorKind::MisplacedVariation => write!(f, "variation before any move"),
+            PgnErrorKind::UnclosedVariation => write!(f, "variation not closed before the end of the game"),
+            PgnErrorKind::Fen(ref e) => write!(f, "{}", e),
+            PgnErrorKind::Move(ref e) => write!(f, "{}", e)
+        }
+    }
+}
+
+impl Error for PgnError {}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    Open,
+    Close,
+    Result(String),
+    MoveNumber,
+    /// a move and the NAG for any "!" or "?" written straight after it
+    San(String, Option<u8>)
+}
+
+/// splits PGN into tokens, keeping track of where each one starts
+struct Lexer<'a> {
+    chars: iter::Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+    /// whether the current line starts with '['
+    tag_line: bool
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str) -> Lexer<'a> {
+        Lexer { chars: text.chars().peekable(), line: 1, column: 1, tag_line: false }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.chars.next()?;
+        if self.column == 1 {
+            self.tag_line = ch == '[';
+        }
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(ch)
+    }
+
+    fn error(&self, kind: PgnErrorKind) -> PgnError {
+        PgnError { line: self.line, column: self.column, kind }
+    }
+
+    /// consumes the text up to and including the given character and
+    /// returns it without that character, or None if the text runs out
+    fn until(&mut self, end: char) -> Option<String> {
+        let mut text = String::new();
+        loop {
+            match self.bump()? {
+                ch if ch == end => return Some(text),
+                ch => text.push(ch)
+            }
+        }
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, first: char, f: F) -> String {
+        let mut text = first.to_string();
+        while let Some(&ch) = self.chars.peek() {
+            if !f(ch) {
+                break;
+            }
+            text.push(ch);
+            self.bump();
+        }
+        text
+    }
+
+    /// skips to the next line that starts with '[' after the header, to
+    /// pick up reading again at the following game after an error. The
+    /// header ends at the first line of movetext or a blank line.
+    fn skip_to_next_game(&mut self) {
+        let mut past_header = !self.tag_line;
+        while let Some(ch) = self.bump() {
+            if ch == '\n' {
+                match self.chars.peek() {
+                    Some(&'[') if past_header => return,
+                    Some(&'[') => {},
+                    _ => past_header = true
+                }
+            }
+        }
+    }
+
+    /// the next token and the line and column it starts at
+    fn next_token(&mut self) -> Result<Option<(Token, usize, usize)>, PgnError> {
+        loop {
+            let (line, column) = (self.line, self.column);
+            let ch = match self.bump() {
+                Some(ch) => ch,
+                None => return Ok(None)
+            };
+            let token = match ch {
+                _ if ch.is_whitespace() || ch == '.' => continue,
+                // the rest of the line is a comment, or an escape at the start of one
+                ';' => Token::Comment(self.until('\n').unwrap_or_default()),
+                '%' if column == 1 => {
+                    self.until('\n');
+                    continue;
+                },
+                '{' => Token::Comment(self.until('}').ok_or_else(|| self.error(PgnErrorKind::Unterminated))?),
+                '(' => Token::Open,
+                ')' => Token::Close,
+                '*' => Token::Result("*".to_owned()),
+                '[' => self.tag(line, column)?,
+                '$' => match self.take_while(ch, |ch| ch.is_ascii_digit())[1..].parse::<u8>() {
+                    Ok(nag) => Token::Nag(nag),
+                    Err(_) => return Err(PgnError { line, column, kind: PgnErrorKind::UnexpectedChar(ch) })
+                },
+                // suffix annotations written apart from their move
+                '!' | '?' => match suffix_nag(&self.take_while(ch, |ch| ch == '!' || ch == '?')) {
+                    Some(nag) => Token::Nag(nag),
+                    None => return Err(PgnError { line, column, kind: PgnErrorKind::UnexpectedChar(ch) })
+                },
+                _ if ch.is_ascii_alphanumeric() => {
+                    let symbol = self.take_while(ch, |ch| ch.is_ascii_alphanumeric() || "_+#=:-/".contains(ch));
+                    match symbol.as_str() {
+                        "1-0" | "0-1" | "1/2-1/2" => Token::Result(symbol),
+                        _ if symbol.bytes().all(|b| b.is_ascii_digit()) => Token::MoveNumber,
+                        _ => {
+                            let suffix = match self.chars.peek() {
+                                Some(&ch) if ch == '!' || ch == '?' => {
+                                    self.bump();
+                                    self.take_while(ch, |ch| ch == '!' || ch == '?')
+                                },
+                                _ => String::new()
+                            };
+                            Token::San(symbol, suffix_nag(&suffix))
+                        }
+                    }
+                },
+                _ => return Err(PgnError { line, column, kind: PgnErrorKind::UnexpectedChar(ch) })
+            };
+            return Ok(Some((token, line, column)))
+        }
+    }
+
+    /// reads the rest of `[Name "value"]`
+    fn tag(&mut self, line: usize, column: usize) -> Result<Token, PgnError> {
+        let error = PgnError { line, column, kind: PgnErrorKind::Tag };
+        let mut name = String::new();
+        while let Some(&ch) = self.chars.peek() {
+            if !ch.is_ascii_alphanumeric() && ch != '_' {
+                break;
+            }
+            name.push(ch);
+            self.bump();
+        }
+        while self.chars.peek().is_some_and(|ch| ch.is_whitespace()) {
+            self.bump();
+        }
+        if name.is_empty() || self.bump() != Some('"') {
+            return Err(error);
+        }
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => break,
+                // only quotes and backslashes are escaped
+                Some('\\') => match self.bump() {
+                    Some(ch) => value.push(ch),
+                    None => return Err(self.error(PgnErrorKind::Unterminated))
+                },
+                Some(ch) => value.push(ch),
+                None => return Err(self.error(PgnErrorKind::Unterminated))
+            }
+        }
+        while self.chars.peek().is_some_and(|&ch| ch == ' ' || ch == '\t') {
+            self.bump();
+        }
+        if self.bump() != Some(']') {
+            return Err(error);
+        }
+        Ok(Token::Tag(name, value))
+    }
+}
+
+/// where moves go at one level of variation nesting
+struct Level {
+    /// the path through the game tree to the last move at this level, or
+    /// to the move before the variation if none has been played in it yet
+    path: Vec<usize>,
+    fresh: bool,
+    /// the positions before and after the last move
+    before: Option<Board>,
+    board: Board,
+    /// a comment to go before the next move
+    comment: Option<String>
+}
+
+impl Level {
+    fn new(path: Vec<usize>, board: Board) -> Level {
+        Level { path, fresh: true, before: None, board, comment: None }
+    }
+}
+
+/// the node at the end of a non-empty path
+fn node_mut<'a>(game: &'a mut Game, path: &[usize]) -> &'a mut Node {
+    let (&last, parents) = path.split_last().unwrap();
+    &mut game.children_mut(parents)[last]
+}
+
+/// adds text to a comment, so that several in a row are kept as one
+fn add_comment(comment: &mut Option<String>, text: &str) {
+    let text = text.trim();
+    match *comment {
+        Some(ref mut comment) => {
+            comment.push(' ');
+            comment.push_str(text);
+        },
+        None => *comment = Some(text.to_owned())
+    }
+}
+
+/// the NAG written as "!", "?!" and so on after a move
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None
+    }
+}
+
+/// Reads games one at a time from PGN text. After an error it skips to the
+/// next game, so one bad game doesn't stop the rest of a file being read.
+pub struct PgnReader<'a> {
+    lexer: Lexer<'a>,
+    /// a tag read while finishing a game that had no result
+    peeked: Option<(Token, usize, usize)>
+}
+
+impl<'a> PgnReader<'a> {
+    pub fn new(text: &'a str) -> PgnReader<'a> {
+        PgnReader { lexer: Lexer::new(text), peeked: None }
+    }
+
+    fn read_game(&mut self) -> Result<Option<Game>, PgnError> {
+        let mut game = Game::new();
+        // the main line is the bottom level
+        let mut levels = vec![Level::new(Vec::new(), game.start)];
+        let mut empty = true;
+        loop {
+            let (token, line, column) = match self.peeked.take() {
+                Some(token) => token,
+                None => match self.lexer.next_token()? {
+                    Some(token) => token,
+                    None => break
+                }
+            };
+            let error = |kind| PgnError { line, column, kind };
+            let depth = levels.len();
+            let level = levels.last_mut().unwrap();
+            match token {
+                Token::Tag(name, value) => {
+                    if !game.moves.is_empty() || depth > 1 {
+                        // the game before had no result
+                        self.peeked = Some((Token::Tag(name, value), line, column));
+                        break;
+                    }
+                    if name == "FEN" {
+                        game.start = Board::from_fen(&value).map_err(|e| error(PgnErrorKind::Fen(e)))?;
+                        level.board = game.start;
+                    }
+                    game.headers.push((name, value));
+                },
+                Token::San(san, nag) => {
+                    let m = level.board.parse_san(&san).map_err(|e| error(PgnErrorKind::Move(e)))?;
+                    let mut node = Node::new(m);
+                    node.comment_before = level.comment.take();
+                    node.nags.extend(nag);
+                    let children = if level.fresh {
+                        game.children_mut(&level.path)
+                    } else {
+                        &mut node_mut(&mut game, &level.path).children
+                    };
+                    children.push(node);
+                    let index = children.len() - 1;
+                    if level.fresh {
+                        level.fresh = false;
+                    }
+                    level.path.push(index);
+                    level.before = Some(level.board);
+                    level.board.make_move(m);
+                },
+                Token::Comment(text) => {
+                    if level.fresh {
+                        add_comment(&mut level.comment, &text);
+                    } else {
+                        add_comment(&mut node_mut(&mut game, &level.path).comment, &text);
+                    }
+                },
+                Token::Nag(nag) => {
+                    if !level.fresh {
+                        node_mut(&mut game, &level.path).nags.push(nag);
+                    }
+                },
+                Token::Open => {
+                    // the variation is an alternative to the last move at this level
+                    let board = level.before.ok_or_else(|| error(PgnErrorKind::MisplacedVariation))?;
+                    let path = level.path[..level.path.len() - 1].to_vec();
+                    levels.push(Level::new(path, board));
+                },
+                Token::Close => {
+                    if levels.len() == 1 {
+                        return Err(error(PgnErrorKind::UnmatchedParen));
+                    }
+                    levels.pop();
+                },
+                Token::Result(result) => {
+                    if levels.len() > 1 {
+                        return Err(error(PgnErrorKind::UnclosedVariation));
+                    }
+                    game.result = Some(result);
+                    return Ok(Some(game))
+                },
+                Token::MoveNumber => {}
+            }
+            empty = false;
+        }
+        if levels.len() > 1 {
+            return Err(self.lexer.error(PgnErrorKind::UnclosedVariation));
+        }
+        Ok(if empty { None } else { Some(game) })
+    }
+}
+
+impl<'a> Iterator for PgnReader<'a> {
+    type Item = Result<Game, PgnError>;
+
+    fn next(&mut self) -> Option<Result<Game, PgnError>> {
+        match self.read_game() {
+            Ok(game) => game.map(Ok),
+            Err(e) => {
+                self.lexer.skip_to_next_game();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// reads every game in the PGN text, stopping at the first error
+pub fn read_games(text: &str) -> Result<Vec<Game>, PgnError> {
+    PgnReader::new(text).collect()
+}
+
+/// the tags every exported game starts with, in order, and their values
+/// when unknown
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [("Event", "?"), ("Site", "?"), ("Date", "????.??.??"),
+                                             ("Round", "?"), ("White", "?"), ("Black", "?"),
+                                             ("Result", "*")];
+
+/// the longest line of movetext written by `to_pgn`
+const LINE_LENGTH: usize = 79;
+
+impl Game {
+    /// writes the game in PGN export format: the seven tag roster then any
+    /// other tags, and the movetext with its comments, NAGs and variations
+    /// wrapped to 79 columns
+    pub fn to_pgn(&self) -> String {
+        let result = self.result.as_deref().or_else(|| self.header("Result")).unwrap_or("*");
+        let mut pgn = String::new();
+        for &(name, default) in &SEVEN_TAG_ROSTER {
+            let value = if name == "Result" { result } else { self.header(name).unwrap_or(default) };
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, escape(value)));
+        }
+        for (name, value) in &self.headers {
+            if !SEVEN_TAG_ROSTER.iter().any(|tag| tag.0 == name) {
+                pgn.push_str(&format!("[{} \"{}\"]\n", name, escape(value)));
+            }
+        }
+        pgn.push('\n');
+
+        let mut tokens = Vec::new();
+        write_line(self.start, &self.moves, true, &mut tokens);
+        tokens.push(result.to_owned());
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_LENGTH {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() && !line.ends_with('(') && token != ")" {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+        pgn
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// splits a comment into words, so that long comments can be wrapped
+fn push_comment(comment: &str, tokens: &mut Vec<String>) {
+    let comment = format!("{{{}}}", comment.replace('}', ""));
+    tokens.extend(comment.split_whitespace().map(str::to_owned));
+}
+
+/// adds the tokens for the first of the nodes, each of the others as a
+/// variation on it, then the line that follows the first
+fn write_line(mut board: Board, mut nodes: &[Node], mut number: bool, tokens: &mut Vec<String>) {
+    while let Some(node) = nodes.first() {
+        if let Some(ref comment) = node.comment_before {
+            push_comment(comment, tokens);
+        }
+        // black's moves are only numbered after something interrupts the line
+        if board.active_color == Color::White {
+            tokens.push(format!("{}.", board.fullmove_number));
+        } else if number {
+            tokens.push(format!("{}...", board.fullmove_number));
+        }
+        tokens.push(board.to_san(node.m));
+        tokens.extend(node.nags.iter().map(|nag| format!("${}", nag)));
+        if let Some(ref comment) = node.comment {
+            push_comment(comment, tokens);
+        }
+        number = node.comment.is_some() || nodes.len() > 1;
+        for variation in &nodes[1..] {
+            tokens.push("(".to_owned());
+            write_line(board, slice::from_ref(variation), true, tokens);
+            tokens.push(")".to_owned());
+        }
+        board.make_move(node.m);
+        nodes = &node.children;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use board::Board;
+    use game::{Game, Node};
+    use pgn::{self, PgnError, PgnErrorKind, PgnReader};
+    use san::SanError;
+
+    const GAME: &str = r#"[Event "Casual \"Game\""]
+[White "Morphy"]
+[Black "Duke Karl / Count Isouard"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 d6 3. d4 Bg4 {This is a weak move
+already.} 4. dxe5 Bxf3 5. Qxf3 dxe5 6. Bc4 Nf6 7. Qb3 $6 (7. Nc3?! ; rest of line
+Qe7 (7... c6)) 7... Qe7 8. Nc3 c6 9. Bg5 b5 10. Nxb5! cxb5 11. Bxb5+ Nbd7
+12. O-O-O Rd8 13. Rxd7 Rxd7 14. Rd1 Qe6 15. Bxd7+ Nxd7 16. Qb8+ Nxb8 17. Rd8# 1-0
+"#;
+
+    #[test]
+    fn test_read_game() {
+        let games = pgn::read_games(GAME).unwrap();
+        assert_eq!(games.len(), 1);
+        let game = &games[0];
+        assert_eq!(game.header("Event"), Some("Casual \"Game\""));
+        assert_eq!(game.header("Black"), Some("Duke Karl / Count Isouard"));
+        assert_eq!(game.header("Site"), None);
+        assert_eq!(game.main_line().len(), 33);
+        assert_eq!(game.result.as_deref(), Some("1-0"));
+        let positions = game.positions().collect::<Vec<_>>();
+        assert_eq!(positions.len(), 34);
+        assert_eq!(positions[33].to_fen(), "1n1Rkb1r/p4ppp/4q3/4p1B1/4P3/8/PPP2PPP/2K5 b k - 1 17");
+        assert!(positions[33].outcome().is_some());
+
+        // 6... Nf6 7. Qb3 $6 (7. Nc3?! {rest of line} Qe7 (7... c6)) 7... Qe7
+        let mut nodes = &game.moves;
+        for _ in 0..12 {
+            nodes = &nodes[0].children;
+        }
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].nags, vec![6]);
+        assert_eq!(nodes[1].nags, vec![6]);
+        assert_eq!(nodes[1].comment.as_deref(), Some("rest of line"));
+        assert_eq!(nodes[1].children.len(), 2);
+        assert_eq!(nodes[0].children.len(), 1);
+        let mut nodes = &game.moves;
+        for _ in 0..5 {
+            nodes = &nodes[0].children;
+        }
+        assert_eq!(nodes[0].comment.as_deref(), Some("This is a weak move\nalready."));
+    }
+
+    #[test]
+    fn test_read_several_games() {
+        let text = format!("{}\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n1. e4 Kd7\n\n{}", GAME,
+                           "[Event \"third\"]\n1. d4 *\n");
+        let games = pgn::read_games(&text).unwrap();
+        assert_eq!(games.len(), 3);
+        // the second game has no result, so it ends at the next game's tags
+        assert_eq!(games[1].result, None);
+        assert_eq!(games[1].main_line().len(), 2);
+        assert_eq!(games[1].positions().last().unwrap().to_fen(), "8/3k4/8/8/4P3/8/8/4K3 w - - 1 2");
+        assert_eq!(games[2].result.as_deref(), Some("*"));
+        assert_eq!(pgn::read_games("  \n").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let error = |line, column, kind| Err(PgnError { line, column, kind });
+        assert_eq!(pgn::read_games("1. e4 e5\n2. Ke3 *"),
+                   error(2, 4, PgnErrorKind::Move(SanError::Illegal("Ke3".to_owned()))));
+        assert_eq!(pgn::read_games("1. e4 ) *"), error(1, 7, PgnErrorKind::UnmatchedParen));
+        assert_eq!(pgn::read_games("1. e4 (1. d4 *"), error(1, 14, PgnErrorKind::UnclosedVariation));
+        assert_eq!(pgn::read_games("(1. d4) *"), error(1, 1, PgnErrorKind::MisplacedVariation));
+        assert_eq!(pgn::read_games("[Event Casual]"), error(1, 1, PgnErrorKind::Tag));
+        assert_eq!(pgn::read_games("1. e4 {unfinished").unwrap_err().kind, PgnErrorKind::Unterminated);
+        assert_eq!(pgn::read_games("1. e4 & *"), error(1, 7, PgnErrorKind::UnexpectedChar('&')));
+        // reading carries on with the next game
+        let results = PgnReader::new("[Event \"a\"]\n1. e5 *\n[Event \"b\"]\n1. e4 *").collect::<Vec<_>>();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_err());
+        assert_eq!(results[1].as_ref().unwrap().header("Event"), Some("b"));
+        // and after a bad tag, with the rest of the header and its moves
+        for text in &["[Event \"a\"]\n[Site bad]\n[Date \"?\"]\n1. e4 *\n\n[Event \"b\"]\n1. e4 *",
+                      "[Event \"a\"]\n[Site bad]\n[Date \"?\"]\n1. e4 *\n[Event \"b\"]\n1. e4 *"] {
+            let results = PgnReader::new(text).collect::<Vec<_>>();
+            assert_eq!(results.len(), 2);
+            assert_eq!(results[0].as_ref().unwrap_err(), &PgnError { line: 2, column: 1, kind: PgnErrorKind::Tag });
+            assert_eq!(results[1].as_ref().unwrap().header("Event"), Some("b"));
+        }
+    }
+
+    #[test]
+    fn test_write_game() {
+        let mut game = Game::from_board(Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap());
+        game.set_header("White", "ruci \"dev\"");
+        let e4 = game.start.parse_san("e4").unwrap();
+        game.push(e4);
+        game.push(game.positions().last().unwrap().parse_san("Kd7").unwrap());
+        let mut e3 = Node::new(game.start.parse_san("e3").unwrap());
+        e3.comment_before = Some("slower".to_owned());
+        e3.nags.push(2);
+        game.moves.push(e3);
+        game.moves[0].comment = Some("best".to_owned());
+        game.result = Some("1/2-1/2".to_owned());
+        assert_eq!(game.to_pgn(), "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n\
+                                   [White \"ruci \\\"dev\\\"\"]\n[Black \"?\"]\n[Result \"1/2-1/2\"]\n\
+                                   [SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n\n\
+                                   1. e4 {best} ({slower} 1. e3 $2) 1... Kd7 1/2-1/2\n");
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let game = pgn::read_games(GAME).unwrap().remove(0);
+        let written = game.to_pgn();
+        assert!(written.lines().all(|line| line.len() <= 79));
+        assert!(written.contains("7. Qb3 $6 (7. Nc3 $6 {rest of line} 7... Qe7 (7... c6))\n7... Qe7"));
+        let read = pgn::read_games(&written).unwrap().remove(0);
+        assert_eq!(read.main_line(), game.main_line());
+        assert_eq!(read.to_pgn(), written);
+    }
+}
@@ -1,6 +1,6 @@
 use color::Color;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum Type {
     Pawn,
     Bishop,
@@ -10,8 +10,38 @@ pub enum Type {
     King
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct Piece {
     pub piece_type: Type,
     pub color: Color
 }
+
+impl Type {
+    /// every piece type, in the same order as the enum
+    pub const ALL: [Type; 6] = [
+        Type::Pawn,
+        Type::Bishop,
+        Type::Knight,
+        Type::Rook,
+        Type::Queen,
+        Type::King
+    ];
+}
+
+impl Piece {
+    /// the FEN letter for the piece, uppercase for white and lowercase for black
+    pub fn to_fen_char(self) -> char {
+        let ch = match self.piece_type {
+            Type::Pawn => 'p',
+            Type::Bishop => 'b',
+            Type::Knight => 'n',
+            Type::Rook => 'r',
+            Type::Queen => 'q',
+            Type::King => 'k'
+        };
+        match self.color {
+            Color::White => ch.to_ascii_uppercase(),
+            Color::Black => ch
+        }
+    }
+}
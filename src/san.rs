@@ -0,0 +1,285 @@
+use std::error::Error;
+use std::fmt;
+
+use board::Board;
+use movegen::MoveList;
+use moves::Move;
+use piece::Type;
+use square::Square;
+
+/// the reasons a string can fail to be read as a move in standard
+/// algebraic notation
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SanError {
+    /// the string does not look like SAN at all
+    Invalid(String),
+    /// no legal move matches the string
+    Illegal(String),
+    /// more than one legal move matches the string
+    Ambiguous(String)
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SanError::Invalid(ref s) => write!(f, "invalid move '{}'", s),
+            SanError::Illegal(ref s) => write!(f, "illegal move '{}'", s),
+            SanError::Ambiguous(ref s) => write!(f, "ambiguous move '{}'", s)
+        }
+    }
+}
+
+impl Error for SanError {}
+
+fn piece_letter(piece_type: Type) -> char {
+    match piece_type {
+        Type::Pawn => 'P',
+        Type::Knight => 'N',
+        Type::Bishop => 'B',
+        Type::Rook => 'R',
+        Type::Queen => 'Q',
+        Type::King => 'K'
+    }
+}
+
+fn piece_type(letter: char) -> Option<Type> {
+    match letter {
+        'N' => Some(Type::Knight),
+        'B' => Some(Type::Bishop),
+        'R' => Some(Type::Rook),
+        'Q' => Some(Type::Queen),
+        'K' => Some(Type::King),
+        _ => None
+    }
+}
+
+impl Board {
+    /// writes the move, which must be legal, in standard algebraic notation
+    /// such as "Nbd2", "exd5", "e8=Q+" or "O-O-O#"
+    pub fn to_san(&self, m: Move) -> String {
+        let mut san = String::new();
+        let moved = match self.piece_at(m.from) {
+            Some(piece) => piece.piece_type,
+            None => return m.to_string()
+        };
+        if m.is_castle() {
+            san.push_str(if m.to.file() > m.from.file() { "O-O" } else { "O-O-O" });
+        } else {
+            let capture = m.is_capture() || m.is_en_passant();
+            if moved == Type::Pawn {
+                if capture {
+                    san.push((b'a' + m.from.file()) as char);
+                }
+            } else {
+                san.push(piece_letter(moved));
+                // name the file, the rank or both, whichever tells the move
+                // apart from the other pieces of the same type that can go there
+                let mut moves = MoveList::new();
+                self.generate_moves(&mut moves);
+                let others = moves.iter().filter(|other| {
+                    other.to == m.to && other.from != m.from && !other.is_castle() &&
+                        self.piece_at(other.from).map(|piece| piece.piece_type) == Some(moved)
+                }).collect::<Vec<_>>();
+                if !others.is_empty() {
+                    if others.iter().all(|other| other.from.file() != m.from.file()) {
+                        san.push((b'a' + m.from.file()) as char);
+                    } else if others.iter().all(|other| other.from.rank() != m.from.rank()) {
+                        san.push((b'1' + m.from.rank()) as char);
+                    } else {
+                        san.push_str(&m.from.to_string());
+                    }
+                }
+            }
+            if capture {
+                san.push('x');
+            }
+            san.push_str(&m.to.to_string());
+            if let Some(promotion) = m.promotion {
+                san.push('=');
+                san.push(piece_letter(promotion));
+            }
+        }
+        if self.gives_check(m) {
+            let board = self.after_move(m);
+            let mut replies = MoveList::new();
+            board.generate_moves(&mut replies);
+            san.push(if replies.is_empty() { '#' } else { '+' });
+        }
+        san
+    }
+
+    /// reads a move in standard algebraic notation, returning it with its
+    /// flags set if it is legal. Check and annotation marks may be left out
+    /// or wrong, castles may use zeros, and a promotion without a piece is
+    /// taken to be a queen.
+    pub fn parse_san(&self, san: &str) -> Result<Move, SanError> {
+        let invalid = || SanError::Invalid(san.to_owned());
+        let text = san.trim_end_matches(|ch| "+#!?".contains(ch));
+        let mut moves = MoveList::new();
+        self.generate_moves(&mut moves);
+
+        let castle = match text {
+            "O-O" | "0-0" => Some(true),
+            "O-O-O" | "0-0-0" => Some(false),
+            _ => None
+        };
+        let candidates = if let Some(kingside) = castle {
+            moves.iter().cloned().filter(|m| m.is_castle() && (m.to.file() > m.from.file()) == kingside)
+                .collect::<Vec<_>>()
+        } else {
+            if !text.is_ascii() {
+                return Err(invalid());
+            }
+            let mut chars = text.chars().collect::<Vec<_>>();
+            let moved = match chars.first().and_then(|&ch| piece_type(ch)) {
+                Some(piece_type) => {
+                    chars.remove(0);
+                    piece_type
+                },
+                None => Type::Pawn
+            };
+            // "e8=Q", "e8Q" or "e8(Q)"
+            chars.retain(|&ch| ch != '=' && ch != '(' && ch != ')');
+            let promotion = match chars.last().and_then(|&ch| piece_type(ch.to_ascii_uppercase())) {
+                Some(piece_type) if moved == Type::Pawn && chars.len() > 2 => {
+                    chars.pop();
+                    Some(piece_type)
+                },
+                _ => None
+            };
+            if chars.len() < 2 {
+                return Err(invalid());
+            }
+            let to = chars[chars.len() - 2..].iter().collect::<String>().parse::<Square>()
+                .map_err(|_| invalid())?;
+            // whatever is left says where the piece came from, and whether it captures
+            let (mut file, mut rank) = (None, None);
+            for &ch in &chars[..chars.len() - 2] {
+                match ch {
+                    'a'..='h' if file.is_none() => file = Some(ch as u8 - b'a'),
+                    '1'..='8' if rank.is_none() => rank = Some(ch as u8 - b'1'),
+                    'x' | ':' | '-' => {},
+                    _ => return Err(invalid())
+                }
+            }
+            moves.iter().cloned().filter(|m| {
+                !m.is_castle() && m.to == to &&
+                    self.piece_at(m.from).map(|piece| piece.piece_type) == Some(moved) &&
+                    file.is_none_or(|file| m.from.file() == file) &&
+                    rank.is_none_or(|rank| m.from.rank() == rank) &&
+                    match m.promotion {
+                        Some(piece_type) => piece_type == promotion.unwrap_or(Type::Queen),
+                        None => promotion.is_none()
+                    }
+            }).collect()
+        };
+        match candidates.len() {
+            0 => Err(SanError::Illegal(san.to_owned())),
+            1 => Ok(candidates[0]),
+            _ => Err(SanError::Ambiguous(san.to_owned()))
+        }
+    }
+}
+
+/// writes a line of moves from the given position in SAN
+pub fn line_to_san(board: &Board, line: &[Move]) -> Vec<String> {
+    let mut board = *board;
+    line.iter().map(|&m| {
+        let san = board.to_san(m);
+        board.make_move(m);
+        san
+    }).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use board::{self, Board};
+    use logic;
+    use movegen::MoveList;
+    use san::{self, SanError};
+
+    fn to_san(fen: &str, m: &str) -> String {
+        let board = Board::from_fen(fen).unwrap();
+        board.to_san(logic::parse_move(&board, m).unwrap())
+    }
+
+    fn parse_san(fen: &str, san: &str) -> Result<String, SanError> {
+        Board::from_fen(fen).unwrap().parse_san(san).map(|m| m.to_string())
+    }
+
+    #[test]
+    fn test_to_san() {
+        assert_eq!(to_san(board::START_FEN, "g1f3"), "Nf3");
+        assert_eq!(to_san(board::START_FEN, "e2e4"), "e4");
+        assert_eq!(to_san("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2", "e4d5"), "exd5");
+        assert_eq!(to_san("rnbqkbnr/p1p1p1pp/3p4/1p2Pp2/3P4/8/PPP2PPP/RNBQKBNR w KQkq f6 0 4", "e5f6"), "exf6");
+        assert_eq!(to_san("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8q"), "axb8=Q+");
+        assert_eq!(to_san("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1"), "O-O");
+        assert_eq!(to_san("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8c8"), "O-O-O");
+        assert_eq!(to_san("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2", "d8h4"), "Qh4#");
+    }
+
+    #[test]
+    fn test_to_san_disambiguation() {
+        // knights on b1 and f3, rooks on e1 and e3
+        let fen = "3k4/8/8/8/8/4RN2/8/1N2R1K1 w - - 0 1";
+        assert_eq!(to_san(fen, "b1d2"), "Nbd2");
+        assert_eq!(to_san(fen, "e1e2"), "R1e2");
+        assert_eq!(to_san("3k4/8/8/8/8/8/8/Q1K4Q w - - 0 1", "h1e4"), "Qe4");
+        // queens on a4, h4 and a1 can all reach d4
+        let fen = "2k5/8/8/8/Q6Q/8/8/Q5K1 w - - 0 1";
+        assert_eq!(to_san(fen, "a4d4"), "Qa4d4");
+        assert_eq!(to_san(fen, "a1d4"), "Q1d4");
+        assert_eq!(to_san(fen, "h4d4"), "Qhd4");
+    }
+
+    #[test]
+    fn test_parse_san() {
+        assert_eq!(parse_san(board::START_FEN, "Nf3"), Ok("g1f3".to_owned()));
+        assert_eq!(parse_san(board::START_FEN, "e4"), Ok("e2e4".to_owned()));
+        assert_eq!(parse_san("3k4/8/8/8/8/4RN2/8/1N2R1K1 w - - 0 1", "Nbd2"), Ok("b1d2".to_owned()));
+        assert_eq!(parse_san("3k4/8/8/8/8/4RN2/8/1N2R1K1 w - - 0 1", "R1e2"), Ok("e1e2".to_owned()));
+        assert_eq!(parse_san("3k4/8/8/8/8/4RN2/8/1N2R1K1 w - - 0 1", "Nd2"),
+                   Err(SanError::Ambiguous("Nd2".to_owned())));
+        assert_eq!(parse_san(board::START_FEN, "e5"), Err(SanError::Illegal("e5".to_owned())));
+        assert_eq!(parse_san(board::START_FEN, "Zf3"), Err(SanError::Invalid("Zf3".to_owned())));
+        assert_eq!(parse_san(board::START_FEN, ""), Err(SanError::Invalid("".to_owned())));
+    }
+
+    #[test]
+    fn test_parse_san_lenient() {
+        let castles = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(parse_san(castles, "O-O"), Ok("e1g1".to_owned()));
+        assert_eq!(parse_san(castles, "0-0-0"), Ok("e1c1".to_owned()));
+        assert_eq!(parse_san(castles, "O-O-O+"), Ok("e1c1".to_owned()));
+        let promotion = "1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(parse_san(promotion, "axb8=N"), Ok("a7b8n".to_owned()));
+        assert_eq!(parse_san(promotion, "axb8R+"), Ok("a7b8r".to_owned()));
+        assert_eq!(parse_san(promotion, "a8"), Ok("a7a8q".to_owned()));
+        // a missing check mark, a pawn capture without the 'x', and annotations
+        assert_eq!(parse_san("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2", "Qh4"),
+                   Ok("d8h4".to_owned()));
+        assert_eq!(parse_san("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2", "ed5!?"),
+                   Ok("e4d5".to_owned()));
+    }
+
+    #[test]
+    fn test_san_round_trip() {
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let mut moves = MoveList::new();
+        board.generate_moves(&mut moves);
+        for &m in moves.iter() {
+            assert_eq!(board.parse_san(&board.to_san(m)), Ok(m));
+        }
+
+        let mut board = Board::from_fen(board::START_FEN).unwrap();
+        let start = board;
+        let mut line = Vec::new();
+        for m in &["e2e4", "e7e5", "g1f3"] {
+            let m = logic::parse_move(&board, m).unwrap();
+            board.make_move(m);
+            line.push(m);
+        }
+        assert_eq!(san::line_to_san(&start, &line), vec!["e4", "e5", "Nf3"]);
+    }
+}
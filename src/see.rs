@@ -0,0 +1,137 @@
+use std::cmp;
+
+use bitboard::{self, Bitboard};
+use board::Board;
+use color::Color;
+use moves::Move;
+use piece::Type;
+use square::Square;
+
+/// piece values in centipawns for exchanges, indexed by `Type as usize`
+pub const SEE_VALUES: [i32; 6] = [100, 330, 320, 500, 900, 20000];
+
+fn value(piece_type: Type) -> i32 {
+    SEE_VALUES[piece_type as usize]
+}
+
+/// The longest possible exchange has one capture per piece on the board.
+const MAX_EXCHANGE: usize = 32;
+
+impl Board {
+    /// Static exchange evaluation: the material the side to move gains, in
+    /// centipawns, if both sides keep recapturing on the target square with
+    /// their least valuable piece and each stops once it would lose by
+    /// carrying on. Sliders behind the capturing pieces join in as the
+    /// pieces in front of them leave. Pins are not taken into account, so
+    /// a pinned defender still recaptures and a winning capture can look
+    /// like a losing one.
+    pub fn see(&self, m: Move) -> i32 {
+        let mut piece_type = match self.piece_at(m.from) {
+            Some(piece) => piece.piece_type,
+            None => return 0
+        };
+        if m.is_castle() {
+            return 0
+        }
+        let mut occupancy = self.occupancy() & !bitboard::from_square(m.from);
+        let mut gain = [0; MAX_EXCHANGE];
+        gain[0] = if m.is_en_passant() {
+            occupancy &= !bitboard::from_square(Square::new(m.to.file(), m.from.rank()));
+            value(Type::Pawn)
+        } else {
+            self.piece_at(m.to).map_or(0, |piece| value(piece.piece_type))
+        };
+        if let Some(promotion) = m.promotion {
+            gain[0] += value(promotion) - value(Type::Pawn);
+            piece_type = promotion;
+        }
+
+        let mut side = self.active_color.opposite();
+        let mut depth = 0;
+        loop {
+            let attackers = self.all_attackers(m.to, occupancy) & occupancy;
+            let (from, next_type) = match self.least_valuable(attackers & self.occupied[side as usize], side) {
+                Some(attacker) => attacker,
+                None => break
+            };
+            // the king can only take last
+            if next_type == Type::King && attackers & self.occupied[side.opposite() as usize] != bitboard::EMPTY {
+                break
+            }
+            depth += 1;
+            // what this side is up if the piece now on the square is taken
+            gain[depth] = value(piece_type) - gain[depth - 1];
+            if depth + 1 == MAX_EXCHANGE {
+                break
+            }
+            occupancy &= !bitboard::from_square(from);
+            piece_type = next_type;
+            side = side.opposite();
+        }
+        // each side takes only if it is better than stopping
+        while depth > 0 {
+            gain[depth - 1] = -cmp::max(-gain[depth - 1], gain[depth]);
+            depth -= 1;
+        }
+        gain[0]
+    }
+
+    /// returns true if the exchange started by the move wins at least the
+    /// threshold, in centipawns
+    pub fn see_ge(&self, m: Move, threshold: i32) -> bool {
+        self.see(m) >= threshold
+    }
+
+    fn all_attackers(&self, square: Square, occupancy: Bitboard) -> Bitboard {
+        self.attackers_to_occupied(square, Color::White, occupancy) |
+            self.attackers_to_occupied(square, Color::Black, occupancy)
+    }
+
+    /// the square and type of the cheapest of the given pieces
+    fn least_valuable(&self, pieces: Bitboard, color: Color) -> Option<(Square, Type)> {
+        for &piece_type in &[Type::Pawn, Type::Knight, Type::Bishop, Type::Rook, Type::Queen, Type::King] {
+            let candidates = pieces & self.pieces_of(color, piece_type);
+            if candidates != bitboard::EMPTY {
+                return Some((Square::from_index(candidates.trailing_zeros() as u8), piece_type))
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use board::Board;
+    use logic;
+
+    fn see(fen: &str, m: &str) -> i32 {
+        let board = Board::from_fen(fen).unwrap();
+        board.see(logic::parse_move(&board, m).unwrap())
+    }
+
+    #[test]
+    fn test_see_simple() {
+        // an undefended pawn, a defended one and a quiet move
+        assert_eq!(see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"), 100);
+        assert_eq!(see("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1", "d1d5"), -800);
+        assert_eq!(see("4k3/8/8/8/8/8/8/3QK3 w - - 0 1", "d1d5"), 0);
+        assert_eq!(see("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 1", "d5e6"), 100);
+    }
+
+    #[test]
+    fn test_see_x_rays() {
+        // the second rook backs up the first
+        assert_eq!(see("3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5"), 100);
+        assert_eq!(see("3rk3/8/8/3p4/8/8/3R4/4K3 w - - 0 1", "d2d5"), -400);
+        // the queen behind the bishop outlasts the rook and queen, so the knight is lost
+        assert_eq!(see("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5"), -220);
+    }
+
+    #[test]
+    fn test_see_ge() {
+        let board = Board::from_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let m = logic::parse_move(&board, "d1d5").unwrap();
+        assert!(!board.see_ge(m, 0));
+        assert!(board.see_ge(m, -800));
+    }
+}
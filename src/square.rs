@@ -0,0 +1,113 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// One of the 64 squares, numbered from a1 = 0 to h8 = 63 so that the
+/// number is also the square's bit in a bitboard.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Square(u8);
+
+impl Square {
+    /// 0 <= file, rank <= 7
+    pub fn new(file: u8, rank: u8) -> Square {
+        debug_assert!(file < 8 && rank < 8);
+        Square(rank * 8 + file)
+    }
+
+    pub fn from_index(index: u8) -> Square {
+        debug_assert!(index < 64);
+        Square(index)
+    }
+
+    /// index of the square in a bitboard, a1 = 0 and h8 = 63
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// 0 for the a file up to 7 for the h file
+    pub fn file(self) -> u8 {
+        self.0 % 8
+    }
+
+    /// 0 for the first rank up to 7 for the eighth
+    pub fn rank(self) -> u8 {
+        self.0 / 8
+    }
+
+    /// every square from a1 to h8, along each rank in turn
+    pub fn all() -> Squares {
+        Squares(0)
+    }
+}
+
+/// iterates over the squares of the board in index order
+pub struct Squares(u8);
+
+impl Iterator for Squares {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Square> {
+        if self.0 >= 64 {
+            return None
+        }
+        self.0 += 1;
+        Some(Square(self.0 - 1))
+    }
+}
+
+/// writes the square in algebraic notation, such as "e4"
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", (b'a' + self.file()) as char, self.rank() + 1)
+    }
+}
+
+/// returned when a string is not a square such as "e4"
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseSquareError(pub String);
+
+impl fmt::Display for ParseSquareError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid square '{}'", self.0)
+    }
+}
+
+impl Error for ParseSquareError {}
+
+impl FromStr for Square {
+    type Err = ParseSquareError;
+
+    fn from_str(s: &str) -> Result<Square, ParseSquareError> {
+        match s.as_bytes() {
+            &[file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Ok(Square::new(file - b'a', rank - b'1')),
+            _ => Err(ParseSquareError(s.to_owned()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use square::{ParseSquareError, Square};
+
+    #[test]
+    fn test_square_coordinates() {
+        let square = Square::new(4, 2);
+        assert_eq!(square.index(), 20);
+        assert_eq!((square.file(), square.rank()), (4, 2));
+        assert_eq!(Square::from_index(63), Square::new(7, 7));
+        assert_eq!(Square::all().count(), 64);
+        assert!(Square::all().enumerate().all(|(i, square)| square.index() == i));
+    }
+
+    #[test]
+    fn test_square_notation() {
+        for square in Square::all() {
+            assert_eq!(square.to_string().parse::<Square>(), Ok(square));
+        }
+        assert_eq!("a1".parse::<Square>(), Ok(Square::new(0, 0)));
+        assert_eq!("h8".parse::<Square>().unwrap().to_string(), "h8");
+        for &s in &["", "e", "e9", "i1", "E4", "e4x", "xe4", "é4"] {
+            assert_eq!(s.parse::<Square>(), Err(ParseSquareError(s.to_owned())));
+        }
+    }
+}
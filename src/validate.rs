@@ -0,0 +1,137 @@
+use std::error::Error;
+use std::fmt;
+
+use bitboard;
+use board::Board;
+use color::Color;
+use piece::{Piece, Type};
+use square::Square;
+
+/// the reasons a position can be impossible to reach in a game
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PositionError {
+    /// the color has this many kings instead of one
+    KingCount(Color, u32),
+    /// a pawn stands on the first or last rank
+    PawnOnBackRank(Square),
+    /// the color may castle on the given side (true for kingside) but its
+    /// king or rook is not on its starting square
+    Castling(Color, bool),
+    /// no pawn can just have double pushed past the en passant square
+    EnPassant(Square),
+    /// the side that just moved has left its king in check
+    OpponentInCheck
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PositionError::KingCount(color, n) => write!(f, "{:?} has {} kings", color, n),
+            PositionError::PawnOnBackRank(square) => write!(f, "pawn on the back rank at {}", square),
+            PositionError::Castling(color, kingside) => {
+                write!(f, "{:?} cannot castle {}", color, if kingside { "kingside" } else { "queenside" })
+            },
+            PositionError::EnPassant(square) => write!(f, "no pawn to take en passant on {}", square),
+            PositionError::OpponentInCheck => write!(f, "the side not to move is in check")
+        }
+    }
+}
+
+impl Error for PositionError {}
+
+impl Board {
+    /// checks that the position could come up in a game: one king each,
+    /// no pawns on the back ranks, castling rights and en passant square
+    /// that match the pieces, and the side that just moved not in check.
+    /// `from_fen` only checks the syntax, so call this on positions from
+    /// outside the engine.
+    pub fn validate(&self) -> Result<(), PositionError> {
+        for &color in &[Color::White, Color::Black] {
+            let kings = self.count(color, Type::King);
+            if kings != 1 {
+                return Err(PositionError::KingCount(color, kings));
+            }
+        }
+        let pawns = self.pieces_of(Color::White, Type::Pawn) | self.pieces_of(Color::Black, Type::Pawn);
+        let back_ranks = 0xff00_0000_0000_00ff;
+        if let Some(square) = bitboard::squares(pawns & back_ranks).next() {
+            return Err(PositionError::PawnOnBackRank(square));
+        }
+
+        for &color in &[Color::White, Color::Black] {
+            let king = self.king_square(color).unwrap();
+            for &kingside in &[true, false] {
+                if !self.castling_availability.get(color, kingside) {
+                    continue;
+                }
+                let rook = self.castling_rook(color, kingside);
+                let home = if color == Color::White { 0 } else { 7 };
+                if king.rank() != home || self.piece_at(rook) != Some(Piece { piece_type: Type::Rook, color }) ||
+                    (rook.file() > king.file()) != kingside {
+                    return Err(PositionError::Castling(color, kingside));
+                }
+            }
+        }
+
+        if let Some(square) = self.en_passant_square {
+            // the pawn went from behind the square to in front of it
+            let (rank, forward) = if self.active_color == Color::White { (5, 1) } else { (2, -1) };
+            let pawn = Piece { piece_type: Type::Pawn, color: self.active_color.opposite() };
+            let valid = square.rank() == rank && self.is_empty(square) &&
+                self.is_empty(Square::new(square.file(), (rank as i8 + forward) as u8)) &&
+                self.piece_at(Square::new(square.file(), (rank as i8 - forward) as u8)) == Some(pawn);
+            if !valid {
+                return Err(PositionError::EnPassant(square));
+            }
+        }
+
+        let opponent = self.active_color.opposite();
+        if self.attackers_to(self.king_square(opponent).unwrap(), self.active_color) != bitboard::EMPTY {
+            return Err(PositionError::OpponentInCheck);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use board::{self, Board};
+    use color::Color;
+    use square::Square;
+    use validate::PositionError;
+
+    fn validate(fen: &str) -> Result<(), PositionError> {
+        Board::from_fen(fen).unwrap().validate()
+    }
+
+    #[test]
+    fn test_validate_legal_positions() {
+        assert_eq!(validate(board::START_FEN), Ok(()));
+        assert_eq!(validate("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3"), Ok(()));
+        assert_eq!(validate("r5kr/8/8/8/8/8/8/RK5R w AHah - 0 1"), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_kings_and_pawns() {
+        assert_eq!(validate("8/8/8/8/8/8/8/4K3 w - - 0 1"), Err(PositionError::KingCount(Color::Black, 0)));
+        assert_eq!(validate("4k3/8/8/8/8/8/8/K3K2K w - - 0 1"), Err(PositionError::KingCount(Color::White, 3)));
+        assert_eq!(validate("4k2P/8/8/8/8/8/8/4K3 w - - 0 1"),
+                   Err(PositionError::PawnOnBackRank(Square::new(7, 7))));
+    }
+
+    #[test]
+    fn test_validate_castling_and_en_passant() {
+        assert_eq!(validate("4k3/8/8/8/8/8/8/4K3 w K - 0 1"), Err(PositionError::Castling(Color::White, true)));
+        assert_eq!(validate("r3k3/8/8/8/8/8/8/4K3 w q - 0 1"), Ok(()));
+        assert_eq!(validate("r7/4k3/8/8/8/8/8/4K3 w q - 0 1"), Err(PositionError::Castling(Color::Black, false)));
+        // the square is behind a pawn of the side to move, or has no pawn in front of it
+        assert_eq!(validate("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1"), Err(PositionError::EnPassant(Square::new(4, 2))));
+        assert_eq!(validate("4k3/8/8/8/8/8/8/4K3 w - e6 0 1"), Err(PositionError::EnPassant(Square::new(4, 5))));
+    }
+
+    #[test]
+    fn test_validate_opponent_in_check() {
+        assert_eq!(validate("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1"), Err(PositionError::OpponentInCheck));
+        assert_eq!(validate("4k3/8/8/8/8/8/8/4R1K1 b - - 0 1"), Ok(()));
+    }
+}
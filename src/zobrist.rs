@@ -1,109 +1,110 @@
-extern crate rand;
-
-use rand::{Rng, thread_rng};
-
-use board::{Board, Location};
+use board::Board;
 use color::Color;
-use piece::Type;
-
-// TODO: increase table size to account for castling rights and en passant
+use moves::Move;
+use piece::Piece;
+use square::Square;
 
-const WHITE_PAWN: usize = 0;
-const WHITE_BISHOP: usize = 1;
-const WHITE_KNIGHT: usize = 2;
-const WHITE_ROOK: usize = 3;
-const WHITE_QUEEN: usize = 4;
-const WHITE_KING: usize = 5;
-const BLACK_PAWN: usize = 6;
-const BLACK_BISHOP: usize = 7;
-const BLACK_KNIGHT: usize = 8;
-const BLACK_ROOK: usize = 9;
-const BLACK_QUEEN: usize = 10;
-const BLACK_KING: usize = 11;
-
-#[derive(Debug)]
-pub struct Table {
-    table: [[[u64; 12]; 8]; 8]
+/// Random numbers used to hash a position. The keys are generated at
+/// compile time from a fixed seed so every board agrees on them.
+pub struct Keys {
+    /// indexed by `Color as usize`, `Type as usize` and the square index
+    pieces: [[[u64; 64]; 6]; 2],
+    /// indexed by the castling rights as a 4 bit mask
+    castling: [u64; 16],
+    /// indexed by the file of the en passant square
+    en_passant: [u64; 8],
+    black_to_move: u64
 }
 
+pub static KEYS: Keys = Keys::generate();
+
 pub struct Entry {
-    pub best_move: (Location, Location),
+    pub best_move: Move,
     pub depth: u8,
     pub evaluation: f64,
-    pub line: Vec<String>
+    pub line: Vec<Move>
 }
 
-impl Table {
-    pub fn new() -> Table {
-        let mut zobrist = Table { table: [[[0; 12]; 8]; 8] };
-        zobrist.table = [[[0; 12]; 8]; 8];
-        let mut rng = rand::thread_rng();
-        // fill the table with random numbers
-        for rank in 0..8 {
-            for file in 0..8 {
-                for i in 0..12 {
-                    zobrist.table[rank][file][i] = rng.gen();
+/// xorshift64* step, usable in a const context
+const fn next_random(state: u64) -> (u64, u64) {
+    let mut x = state;
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    (x, x.wrapping_mul(0x2545_f491_4f6c_dd1d))
+}
+
+impl Keys {
+    const fn generate() -> Keys {
+        let mut keys = Keys {
+            pieces: [[[0; 64]; 6]; 2],
+            castling: [0; 16],
+            en_passant: [0; 8],
+            black_to_move: 0
+        };
+        let mut state = 0x9e37_79b9_7f4a_7c15;
+        let mut random;
+
+        let mut color = 0;
+        while color < 2 {
+            let mut piece_type = 0;
+            while piece_type < 6 {
+                let mut square = 0;
+                while square < 64 {
+                    (state, random) = next_random(state);
+                    keys.pieces[color][piece_type][square] = random;
+                    square += 1;
                 }
+                piece_type += 1;
             }
+            color += 1;
+        }
+        let mut i = 0;
+        while i < 16 {
+            (state, random) = next_random(state);
+            keys.castling[i] = random;
+            i += 1;
         }
-        zobrist
+        i = 0;
+        while i < 8 {
+            (state, random) = next_random(state);
+            keys.en_passant[i] = random;
+            i += 1;
+        }
+        (_, keys.black_to_move) = next_random(state);
+        keys
     }
 
-    pub fn hash(&self, board: &Board) -> u64 {
-        let mut hash = 0u64;
-        for rank in 0..8 {
-            for file in 0..8 {
-                if let Some(p) = board.board[rank][file] {
-                    match p.piece_type {
-                        Type::Pawn => {
-                            if p.color == Color::White {
-                                hash ^= self.table[rank][file][WHITE_PAWN];
-                            } else {
-                                hash ^= self.table[rank][file][BLACK_PAWN];
-                            }
-                        },
-                        Type::Bishop => {
-                            if p.color == Color::White {
-                                hash ^= self.table[rank][file][WHITE_BISHOP];
-                            } else {
-                                hash ^= self.table[rank][file][BLACK_BISHOP];
-                            }
-                        },
-                        Type::Knight => {
-                            if p.color == Color::White {
-                                hash ^= self.table[rank][file][WHITE_KNIGHT];
-                            } else {
-                                hash ^= self.table[rank][file][BLACK_KNIGHT];
-                            }
-                        },
-                        Type::Rook => {
-                            if p.color == Color::White {
-                                hash ^= self.table[rank][file][WHITE_ROOK];
-                            } else {
-                                hash ^= self.table[rank][file][BLACK_ROOK];
-                            }
-                        },
-                        Type::Queen => {
-                            if p.color == Color::White {
-                                hash ^= self.table[rank][file][WHITE_QUEEN];
-                            } else {
-                                hash ^= self.table[rank][file][BLACK_QUEEN];
-                            }
-                        },
-                        Type::King => {
-                            if p.color == Color::White {
-                                hash ^= self.table[rank][file][WHITE_KING];
-                            } else {
-                                hash ^= self.table[rank][file][BLACK_KING];
-                            }
-                        },
-                    }
-                }
-            }
+    pub fn piece(&self, piece: Piece, square: Square) -> u64 {
+        self.pieces[piece.color as usize][piece.piece_type as usize][square.index()]
+    }
+
+    pub fn castling(&self, rights: usize) -> u64 {
+        self.castling[rights]
+    }
+
+    pub fn en_passant(&self, square: Option<Square>) -> u64 {
+        match square {
+            None => 0,
+            Some(l) => self.en_passant[l.file() as usize]
         }
-        hash
     }
 
-    // TODO
-    // pub fn update_hash(board: &Board, current: u64, move
+    pub fn black_to_move(&self) -> u64 {
+        self.black_to_move
+    }
+}
+
+/// computes the hash of a board from scratch
+pub fn hash(board: &Board) -> u64 {
+    let mut hash = 0u64;
+    for (square, piece) in board.pieces() {
+        hash ^= KEYS.piece(piece, square);
+    }
+    hash ^= KEYS.castling(board.castling_availability.bits());
+    hash ^= KEYS.en_passant(board.en_passant_target());
+    if board.active_color == Color::Black {
+        hash ^= KEYS.black_to_move();
+    }
+    hash
 }
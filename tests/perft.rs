@@ -0,0 +1,43 @@
+extern crate ruci;
+
+use ruci::board::Board;
+use ruci::perft::{self, Options};
+
+/// positions with well known node counts, each picked to catch a different
+/// kind of move generation bug
+const POSITIONS: [(&str, &[u64]); 6] = [
+    ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", &[20, 400, 8902, 197281]),
+    // kiwipete: castling through and out of attacks, and pins
+    ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", &[48, 2039, 97862]),
+    // en passant captures that would expose the king along the rank
+    ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", &[14, 191, 2812, 43238, 674624]),
+    // promotions, and castling rights lost to captured rooks
+    ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", &[6, 264, 9467, 422333]),
+    ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", &[44, 1486, 62379]),
+    ("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", &[46, 2079, 89890]),
+];
+
+#[test]
+fn test_perft_table() {
+    for &(fen, counts) in &POSITIONS {
+        let board = Board::from_fen(fen).unwrap();
+        for (depth, &nodes) in counts.iter().enumerate() {
+            assert_eq!(perft::perft(&board, depth as u8 + 1, Options::default()), nodes, "{} at depth {}", fen, depth + 1);
+        }
+    }
+}
+
+#[test]
+fn test_perft_hashed_and_threaded() {
+    let options = Options { hash: true, threads: 4 };
+    for &(fen, counts) in &POSITIONS {
+        let board = Board::from_fen(fen).unwrap();
+        assert_eq!(perft::perft(&board, counts.len() as u8, options), counts[counts.len() - 1], "{}", fen);
+    }
+}
+
+#[test]
+fn test_perft_chess960() {
+    let board = Board::from_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9").unwrap();
+    assert_eq!(perft::perft(&board, 4, Options { hash: true, threads: 2 }), 326672);
+}